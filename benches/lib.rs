@@ -1,12 +1,14 @@
+#![feature(test)]
+
 extern crate test;
 extern crate entity_system;
 
-#[deriving(Clone)]
+#[derive(Clone)]
 struct TestComponent {
     name: &'static str,
-} 
+}
 
-#[deriving(Clone)]
+#[derive(Clone)]
 struct OtherComponent {
     name: &'static str,
 }
@@ -18,7 +20,7 @@ fn bench_insert_of_5000(b: &mut test::Bencher) {
 
     b.iter(|| {
         let mut cm = entity_system::ComponentManager::new();
-        for _ in range(0u32, 5000) {
+        for _ in 0u32..5000 {
             cm.insert(entity, TestComponent{name: "test"});
         }
     });
@@ -29,11 +31,11 @@ fn bench_find_in_5000(b: &mut test::Bencher) {
     let mut em = entity_system::EntityManager::new();
     let mut cm = entity_system::ComponentManager::new();
 
-    for _ in range(0u32, 5000) {
+    for _ in 0u32..5000 {
         let entity = em.create();
         cm.insert(entity, TestComponent{name: "test"});
         cm.insert(entity, OtherComponent{name: "other"});
-    } 
+    }
 
     b.iter(|| {
         let results = cm.find::<OtherComponent>();
@@ -43,6 +45,24 @@ fn bench_find_in_5000(b: &mut test::Bencher) {
     })
 }
 
+#[bench]
+fn bench_iter_in_5000(b: &mut test::Bencher) {
+    let mut em = entity_system::EntityManager::new();
+    let mut cm = entity_system::ComponentManager::new();
+
+    for _ in 0u32..5000 {
+        let entity = em.create();
+        cm.insert(entity, TestComponent{name: "test"});
+        cm.insert(entity, OtherComponent{name: "other"});
+    }
+
+    b.iter(|| {
+        for meta in cm.iter::<OtherComponent>() {
+            test::black_box(meta.component.name);
+        }
+    })
+}
+
 #[bench]
 fn bench_find_for_in_5000(b: &mut test::Bencher) {
     let mut em = entity_system::EntityManager::new();
@@ -52,11 +72,11 @@ fn bench_find_for_in_5000(b: &mut test::Bencher) {
     cm.insert(entity, TestComponent{name: "test"});
     cm.insert(entity, OtherComponent{name: "other"});
 
-    for _ in range(0u32, 5000) {
+    for _ in 0u32..5000 {
         let entity = em.create();
         cm.insert(entity, TestComponent{name: "test"});
         cm.insert(entity, OtherComponent{name: "other"});
-    } 
+    }
 
     b.iter(|| {
         let results = cm.find_for::<OtherComponent>(entity);
@@ -66,6 +86,28 @@ fn bench_find_for_in_5000(b: &mut test::Bencher) {
     })
 }
 
+#[bench]
+fn bench_iter_for_in_5000(b: &mut test::Bencher) {
+    let mut em = entity_system::EntityManager::new();
+    let mut cm = entity_system::ComponentManager::new();
+
+    let entity = em.create();
+    cm.insert(entity, TestComponent{name: "test"});
+    cm.insert(entity, OtherComponent{name: "other"});
+
+    for _ in 0u32..5000 {
+        let entity = em.create();
+        cm.insert(entity, TestComponent{name: "test"});
+        cm.insert(entity, OtherComponent{name: "other"});
+    }
+
+    b.iter(|| {
+        for c in cm.iter_for::<OtherComponent>(entity) {
+            test::black_box(c.name);
+        }
+    })
+}
+
 #[bench]
 fn bench_get_in_5000(b: &mut test::Bencher) {
     let mut em = entity_system::EntityManager::new();
@@ -75,11 +117,11 @@ fn bench_get_in_5000(b: &mut test::Bencher) {
     cm.insert(entity, TestComponent{name: "test"});
     cm.insert(entity, OtherComponent{name: "other"});
 
-    for _ in range(0u32, 5000) {
+    for _ in 0u32..5000 {
         let entity = em.create();
         cm.insert(entity, TestComponent{name: "test"});
         cm.insert(entity, OtherComponent{name: "other"});
-    } 
+    }
 
     b.iter(|| {
         let c = cm.get::<OtherComponent>(entity);
@@ -92,11 +134,11 @@ fn bench_find_mut_in_5000(b: &mut test::Bencher) {
     let mut em = entity_system::EntityManager::new();
     let mut cm = entity_system::ComponentManager::new();
 
-    for _ in range(0u32, 5000) {
+    for _ in 0u32..5000 {
         let entity = em.create();
         cm.insert(entity, TestComponent{name: "test"});
         cm.insert(entity, OtherComponent{name: "other"});
-    } 
+    }
 
     b.iter(|| {
         let mut results = cm.find_mut::<OtherComponent>();
@@ -106,6 +148,24 @@ fn bench_find_mut_in_5000(b: &mut test::Bencher) {
     })
 }
 
+#[bench]
+fn bench_iter_mut_in_5000(b: &mut test::Bencher) {
+    let mut em = entity_system::EntityManager::new();
+    let mut cm = entity_system::ComponentManager::new();
+
+    for _ in 0u32..5000 {
+        let entity = em.create();
+        cm.insert(entity, TestComponent{name: "test"});
+        cm.insert(entity, OtherComponent{name: "other"});
+    }
+
+    b.iter(|| {
+        for mut meta in cm.iter_mut::<OtherComponent>() {
+            test::black_box(&mut meta.component.name);
+        }
+    })
+}
+
 #[bench]
 fn bench_find_for_mut_in_5000(b: &mut test::Bencher) {
     let mut em = entity_system::EntityManager::new();
@@ -115,11 +175,11 @@ fn bench_find_for_mut_in_5000(b: &mut test::Bencher) {
     cm.insert(entity, TestComponent{name: "test"});
     cm.insert(entity, OtherComponent{name: "other"});
 
-    for _ in range(0u32, 5000) {
+    for _ in 0u32..5000 {
         let entity = em.create();
         cm.insert(entity, TestComponent{name: "test"});
         cm.insert(entity, OtherComponent{name: "other"});
-    } 
+    }
 
     b.iter(|| {
         let mut results = cm.find_for_mut::<OtherComponent>(entity);
@@ -138,14 +198,69 @@ fn bench_get_mut_in_5000(b: &mut test::Bencher) {
     cm.insert(entity, TestComponent{name: "test"});
     cm.insert(entity, OtherComponent{name: "other"});
 
-    for _ in range(0u32, 5000) {
+    for _ in 0u32..5000 {
         let entity = em.create();
         cm.insert(entity, TestComponent{name: "test"});
         cm.insert(entity, OtherComponent{name: "other"});
-    } 
+    }
 
     b.iter(|| {
         let c = cm.get_mut::<OtherComponent>(entity);
         c.name;
     })
 }
+
+fn toggle_5000(b: &mut test::Bencher, kind: entity_system::StorageKind) {
+    let mut em = entity_system::EntityManager::new();
+    let mut cm = entity_system::ComponentManager::with_archetypes();
+    cm.register::<OtherComponent>(kind);
+    let entities: Vec<_> = (0u32..5000).map(|_| em.create()).collect();
+
+    for &entity in entities.iter() {
+        cm.insert(entity, TestComponent{name: "test"});
+    }
+
+    b.iter(|| {
+        for &entity in entities.iter() {
+            cm.insert(entity, OtherComponent{name: "other"});
+        }
+        for &entity in entities.iter() {
+            test::black_box(cm.remove_for::<OtherComponent>(entity));
+        }
+    });
+}
+
+#[bench]
+fn bench_dense_toggle_in_5000(b: &mut test::Bencher) {
+    toggle_5000(b, entity_system::StorageKind::Dense);
+}
+
+#[bench]
+fn bench_sparse_set_toggle_in_5000(b: &mut test::Bencher) {
+    toggle_5000(b, entity_system::StorageKind::SparseSet);
+}
+
+#[bench]
+fn bench_insert_pairs_of_5000(b: &mut test::Bencher) {
+    b.iter(|| {
+        let mut em = entity_system::EntityManager::new();
+        let mut cm = entity_system::ComponentManager::with_archetypes();
+        for _ in 0u32..5000 {
+            let entity = em.create();
+            cm.insert(entity, TestComponent{name: "test"});
+            cm.insert(entity, OtherComponent{name: "other"});
+        }
+    });
+}
+
+#[bench]
+fn bench_insert_bundles_of_5000(b: &mut test::Bencher) {
+    b.iter(|| {
+        let mut em = entity_system::EntityManager::new();
+        let mut cm = entity_system::ComponentManager::with_archetypes();
+        for _ in 0u32..5000 {
+            let entity = em.create();
+            cm.insert_bundle(entity, (TestComponent{name: "test"}, OtherComponent{name: "other"}));
+        }
+    });
+}
@@ -0,0 +1,72 @@
+use component::{Component, ComponentManager};
+use entity::{EntityId, EntityManager};
+use error::Result;
+
+/// Composes an entity in one expression, see `World::build_entity`.
+///
+/// The entity is created up front and components are inserted as they are
+/// given; the name is set by `build`.
+///
+/// # Example
+///
+/// ```rust
+/// let player = world.build_entity()
+///     .with(Position{x: 0, y: 0})
+///     .with(Sprite{image: "player.png"})
+///     .named("player")
+///     .build();
+/// ```
+pub struct EntityBuilder<'a> {
+    em: &'a mut EntityManager,
+    cm: &'a mut ComponentManager,
+    id: EntityId,
+    name: Option<String>,
+}
+
+impl<'a> EntityBuilder<'a> {
+    /// Start building a new entity in the given managers
+    pub fn new(em: &'a mut EntityManager, cm: &'a mut ComponentManager) -> EntityBuilder<'a>
+    {
+        let id = em.create();
+        EntityBuilder{em, cm, id, name: None}
+    }
+
+    pub fn with<T>(self, component: T) -> EntityBuilder<'a>
+        where T: Component
+    {
+        self.cm.insert(self.id, component);
+        self
+    }
+
+    pub fn named<N>(mut self, name: N) -> EntityBuilder<'a>
+        where N: Into<String>
+    {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Finish the entity, returning its id.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name is taken and the name policy is
+    /// `NamePolicy::Error`.
+    pub fn build(self) -> EntityId
+    {
+        self.try_build().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Finish the entity, returning its id. If the name cannot be given,
+    /// the entity is destroyed again and the error returned.
+    pub fn try_build(self) -> Result<EntityId>
+    {
+        if let Some(name) = self.name {
+            if let Err(err) = self.em.rename(self.id, name) {
+                self.em.destroy(self.id);
+                self.cm.purge(self.id);
+                return Err(err);
+            }
+        }
+        Ok(self.id)
+    }
+}
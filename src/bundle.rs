@@ -0,0 +1,74 @@
+use std::any::TypeId;
+
+use component::{Component, ComponentManager};
+use entity::EntityId;
+use storage::{AnyColumn, Column, Table};
+
+/// A tuple of components inserted together, see
+/// `ComponentManager::insert_bundle` and `World::spawn_batch`.
+///
+/// Implemented for tuples of up to six components.
+pub trait Bundle: 'static {
+    /// The component types, in tuple order
+    fn types() -> Vec<TypeId>;
+
+    /// An empty column for each component type
+    fn columns() -> Vec<(TypeId, Box<dyn AnyColumn>)>;
+
+    /// Start a row of a table holding exactly the bundle's types, which
+    /// must all differ
+    fn push_row(self, table: &mut Table, id: EntityId, tick: u64);
+
+    /// Insert the components one at a time
+    fn insert(self, cm: &mut ComponentManager, id: EntityId);
+
+    /// Tell hooks about the components after `push_row`
+    fn notify_inserted(cm: &mut ComponentManager, id: EntityId);
+}
+
+macro_rules! impl_bundle {
+    ($(($component:ident, $value:ident)),+) => {
+        impl<$($component),+> Bundle for ($($component,)+)
+            where $($component: Component),+
+        {
+            fn types() -> Vec<TypeId>
+            {
+                vec![$(TypeId::of::<$component>()),+]
+            }
+
+            fn columns() -> Vec<(TypeId, Box<dyn AnyColumn>)>
+            {
+                vec![$((TypeId::of::<$component>(), Box::new(Column::<$component>::new()))),+]
+            }
+
+            fn push_row(self, table: &mut Table, id: EntityId, tick: u64)
+            {
+                let ($($value,)+) = self;
+                table.push_row(id);
+                $(
+                    table.column_mut::<$component>()
+                        .expect("table to hold the bundle's types")
+                        .push_row(id, $value, tick);
+                )+
+            }
+
+            fn insert(self, cm: &mut ComponentManager, id: EntityId)
+            {
+                let ($($value,)+) = self;
+                $(cm.insert(id, $value);)+
+            }
+
+            fn notify_inserted(cm: &mut ComponentManager, id: EntityId)
+            {
+                $(cm.notify_inserted::<$component>(id);)+
+            }
+        }
+    }
+}
+
+impl_bundle!((A, a));
+impl_bundle!((A, a), (B, b));
+impl_bundle!((A, a), (B, b), (C, c));
+impl_bundle!((A, a), (B, b), (C, c), (D, d));
+impl_bundle!((A, a), (B, b), (C, c), (D, d), (E, e));
+impl_bundle!((A, a), (B, b), (C, c), (D, d), (E, e), (F, f));
@@ -0,0 +1,192 @@
+use std::any::TypeId;
+use std::collections::hash_map::HashMap;
+use std::ops::{Deref, DerefMut};
+
+use component::ComponentManager;
+use entity::EntityId;
+
+/// The change ticks at which a component was added and last changed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ticks {
+    pub added: u64,
+    pub changed: u64,
+}
+
+impl Ticks {
+    pub fn new(tick: u64) -> Ticks
+    {
+        Ticks{added: tick, changed: tick}
+    }
+}
+
+/// Mutable access to a component that marks it changed only when written
+/// through.
+///
+/// Reading through the guard leaves the component's change tick alone, so
+/// `ComponentManager::changed_since` reports just the components that were
+/// actually modified.
+pub struct Mut<'a, T: 'a> {
+    value: &'a mut T,
+    ticks: &'a mut Ticks,
+    tick: u64,
+}
+
+impl<'a, T> Mut<'a, T> {
+    pub(crate) fn new(value: &'a mut T, ticks: &'a mut Ticks, tick: u64) -> Mut<'a, T>
+    {
+        Mut{value, ticks, tick}
+    }
+
+    /// Mark the component changed and give up the guard for a plain
+    /// reference
+    pub fn into_inner(self) -> &'a mut T
+    {
+        self.ticks.changed = self.tick;
+        self.value
+    }
+}
+
+impl<'a, T> Deref for Mut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T
+    {
+        self.value
+    }
+}
+
+impl<'a, T> DerefMut for Mut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T
+    {
+        self.ticks.changed = self.tick;
+        self.value
+    }
+}
+
+/// The current change tick and a log of removed components, kept only for
+/// the types whose removals are tracked
+pub(crate) struct Changes {
+    tick: u64,
+    removed: HashMap<TypeId, Vec<(EntityId, u64)>>,
+}
+
+impl Changes {
+    pub fn new() -> Changes
+    {
+        Changes {
+            tick: 1,
+            removed: HashMap::new(),
+        }
+    }
+
+    pub fn tick(&self) -> u64
+    {
+        self.tick
+    }
+
+    pub fn track_removals(&mut self, type_id: TypeId)
+    {
+        self.removed.entry(type_id).or_default();
+    }
+
+    pub fn record_removed(&mut self, type_id: TypeId, id: EntityId)
+    {
+        if let Some(removed) = self.removed.get_mut(&type_id) {
+            removed.push((id, self.tick));
+        }
+    }
+}
+
+impl ComponentManager {
+    /// The tick stamped onto components as they are added, changed or
+    /// removed. Starts at 1, so `changed_since(0)` sees every change.
+    pub fn change_tick(&self) -> u64
+    {
+        self.changes().tick
+    }
+
+    /// Close the current change tick, returning it. Changes made from now on
+    /// are newer than the returned tick.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let since = self.last_seen;
+    /// self.last_seen = cm.increment_change_tick();
+    /// for entity in cm.changed_since::<Position>(since) {
+    ///     renderer.move_sprite(entity, cm.get::<Position>(entity));
+    /// }
+    /// ```
+    pub fn increment_change_tick(&mut self) -> u64
+    {
+        let changes = self.changes_mut();
+        changes.tick += 1;
+        changes.tick - 1
+    }
+
+    /// Entities that gained a component of type T after `tick`
+    pub fn added_since<T>(&self, tick: u64) -> Vec<EntityId>
+        where T: 'static
+    {
+        self.entities_where::<T, _>(|ticks| ticks.added > tick)
+    }
+
+    /// Entities whose components of type T were added or written after
+    /// `tick`
+    pub fn changed_since<T>(&self, tick: u64) -> Vec<EntityId>
+        where T: 'static
+    {
+        self.entities_where::<T, _>(|ticks| ticks.changed > tick)
+    }
+
+    /// Start logging removals of components of type T, for `removed_since`
+    pub fn track_removals<T>(&mut self)
+        where T: 'static
+    {
+        self.changes_mut().track_removals(TypeId::of::<T>());
+    }
+
+    /// Entities that lost a component of type T after `tick`, including
+    /// purged entities.
+    ///
+    /// Only removals of types passed to `track_removals` are logged, from
+    /// the moment they are tracked, so a manager that never asks for them
+    /// keeps no log. Logged removals are remembered until `forget_removed`
+    /// drops them; call it with ticks every reader has seen to keep the log
+    /// from growing.
+    pub fn removed_since<T>(&self, tick: u64) -> Vec<EntityId>
+        where T: 'static
+    {
+        match self.changes().removed.get(&TypeId::of::<T>()) {
+            Some(removed) => removed.iter()
+                .filter(|&&(_, removed)| removed > tick)
+                .map(|&(id, _)| id)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Forget the removals recorded at or before `tick`
+    pub fn forget_removed(&mut self, tick: u64)
+    {
+        for removed in self.changes_mut().removed.values_mut() {
+            removed.retain(|&(_, removed)| removed > tick);
+        }
+    }
+
+    fn entities_where<T, F>(&self, matches: F) -> Vec<EntityId>
+        where T: 'static, F: Fn(&Ticks) -> bool
+    {
+        let mut entities = Vec::new();
+        for table in self.tables() {
+            if let Some(column) = table.column::<T>() {
+                for (row, &id) in table.entities().iter().enumerate() {
+                    if column.ticks(row).any(&matches) {
+                        entities.push(id);
+                    }
+                }
+            }
+        }
+        entities
+    }
+}
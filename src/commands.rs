@@ -0,0 +1,139 @@
+use component::{Component, ComponentManager};
+use entity::{EntityId, EntityManager};
+
+/// An entity named in a `Commands` buffer: either one that already exists
+/// or one the buffer is going to create
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CommandEntity {
+    Existing(EntityId),
+    /// The nth entity created by the buffer since it was last applied
+    Created(usize),
+}
+
+impl From<EntityId> for CommandEntity {
+    fn from(id: EntityId) -> CommandEntity
+    {
+        CommandEntity::Existing(id)
+    }
+}
+
+type Apply = Box<dyn FnOnce(&mut ComponentManager, EntityId) + Send>;
+
+enum Command {
+    Create,
+    Destroy(CommandEntity),
+    Change(CommandEntity, Apply),
+}
+
+/// A buffer of structural changes to apply later, for when the managers are
+/// borrowed, such as inside a `find_mut` loop.
+///
+/// Changes are applied in the order they were recorded. Entities created by
+/// the buffer can be named in later commands through the `CommandEntity`
+/// that `create` returns, which stays valid until the buffer is applied.
+/// Inserts and removals for an entity that is no longer alive by then, such
+/// as one destroyed by an earlier command, are skipped.
+///
+/// A processor can keep a buffer of its own and hand it to its `World`
+/// through `Processor::commands`, to be applied once the processor's stage
+/// of the tick has run.
+///
+/// # Example
+///
+/// ```rust
+/// let mut commands = Commands::new();
+/// for meta in cm.iter::<Health>() {
+///     if meta.component.hp == 0 {
+///         let corpse = commands.create();
+///         commands.insert(corpse, Corpse{of: meta.entity});
+///         commands.destroy(meta.entity);
+///     }
+/// }
+/// commands.apply(&mut em, &mut cm);
+/// ```
+#[derive(Default)]
+pub struct Commands {
+    commands: Vec<Command>,
+    created: usize,
+}
+
+impl Commands {
+    pub fn new() -> Commands
+    {
+        Commands::default()
+    }
+
+    /// Create an entity
+    pub fn create(&mut self) -> CommandEntity
+    {
+        self.commands.push(Command::Create);
+        self.created += 1;
+        CommandEntity::Created(self.created - 1)
+    }
+
+    /// Destroy an entity and purge its components
+    pub fn destroy<E>(&mut self, entity: E)
+        where E: Into<CommandEntity>
+    {
+        self.commands.push(Command::Destroy(entity.into()));
+    }
+
+    /// Insert a component. Buffers can be kept by parallel processors, so
+    /// the component must be `Send`.
+    pub fn insert<E, T>(&mut self, entity: E, component: T)
+        where E: Into<CommandEntity>, T: Component + Send
+    {
+        self.commands.push(Command::Change(entity.into(), Box::new(move |cm: &mut ComponentManager, id| {
+            cm.insert(id, component);
+        })));
+    }
+
+    /// Remove every component of type T from an entity
+    pub fn remove<T, E>(&mut self, entity: E)
+        where T: Component, E: Into<CommandEntity>
+    {
+        self.commands.push(Command::Change(entity.into(), Box::new(|cm: &mut ComponentManager, id| {
+            cm.remove_for::<T>(id);
+        })));
+    }
+
+    /// The number of commands waiting to be applied
+    pub fn len(&self) -> usize
+    {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.commands.is_empty()
+    }
+
+    /// Apply and forget every recorded command, in order. Returns the ids of
+    /// the entities the buffer created.
+    pub fn apply(&mut self, em: &mut EntityManager, cm: &mut ComponentManager) -> Vec<EntityId>
+    {
+        let mut created = Vec::with_capacity(self.created);
+        let resolve = |created: &[EntityId], entity| match entity {
+            CommandEntity::Existing(id) => id,
+            CommandEntity::Created(n) => created[n],
+        };
+        for command in self.commands.drain(..) {
+            match command {
+                Command::Create => created.push(em.create()),
+                Command::Destroy(entity) => {
+                    let id = resolve(&created, entity);
+                    em.destroy(id);
+                    cm.purge(id);
+                },
+                Command::Change(entity, apply) => {
+                    let id = resolve(&created, entity);
+                    if em.is_alive(id) {
+                        apply(cm, id);
+                    }
+                },
+            }
+        }
+        self.created = 0;
+        created
+    }
+}
@@ -0,0 +1,888 @@
+use std::collections::hash_map::HashMap;
+use std::any::{type_name, Any, TypeId};
+
+use serde::{Deserialize, Serialize};
+
+use entity::{EntityId, EntityManager};
+use error::{EsError, Result};
+use bundle::Bundle;
+use change::{Changes, Mut};
+use hooks::Hooks;
+use snapshot::{Cloners, Storage};
+use query::{Filter, Query, QueryIter};
+use registry::Registry;
+use storage::{AnyColumn, Column, StorageKind, Table, Iter, IterMut, IterFor, IterForMut};
+
+/// Any type that can be stored as a component. This is implemented for
+/// every `'static` type.
+///
+/// Only types that are `Send` and `Sync` can be declared in the `Access` of
+/// a `ParallelProcessor`, as those run on other threads.
+pub trait Component: Any {}
+
+impl<T> Component for T
+    where T: Any
+{}
+
+/// A relationship between entity and component
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EntityMeta<T> {
+    pub entity: EntityId,
+    pub component: T,
+}
+
+/// A component type an entity holds, see `ComponentManager::component_types_of`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ComponentType<'a> {
+    pub type_id: TypeId,
+    /// The type's Rust name, as given by `std::any::type_name`
+    pub type_name: &'static str,
+    /// The name the type is registered under in the `Registry`, if any
+    pub name: Option<&'a str>,
+}
+
+/// The ComponentManager manages the relationships between entities and components.
+///
+/// Components are stored in tables of packed, row-aligned columns. By default
+/// each component type gets a table of its own. A manager created with
+/// `with_archetypes` instead keeps every entity in the single table for its
+/// exact set of component types (its archetype), moving it between tables as
+/// components are inserted and removed. Joins over archetype tables walk
+/// their columns in step rather than looking each entity up per type.
+///
+/// Types registered with a `StorageKind` other than `Dense` always keep a
+/// table of their own.
+pub struct ComponentManager {
+    archetypes: bool,
+    kinds: HashMap<TypeId, StorageKind>,
+    tables: Vec<Table>,
+    table_index: HashMap<Vec<TypeId>, usize>,
+    /// The tables holding a column for each type currently stored
+    type_tables: HashMap<TypeId, Vec<usize>>,
+    /// The table each entity lives in, when using archetypes
+    locations: HashMap<EntityId, usize>,
+    changes: Changes,
+    hooks: Hooks,
+    cloners: Cloners,
+}
+
+impl Default for ComponentManager {
+    fn default() -> ComponentManager
+    {
+        ComponentManager::new()
+    }
+}
+
+impl ComponentManager {
+    pub fn new() -> ComponentManager
+    {
+        ComponentManager {
+            archetypes: false,
+            kinds: HashMap::new(),
+            tables: Vec::new(),
+            table_index: HashMap::new(),
+            type_tables: HashMap::new(),
+            locations: HashMap::new(),
+            changes: Changes::new(),
+            hooks: Hooks::new(),
+            cloners: Cloners::new(),
+        }
+    }
+
+    /// Create a ComponentManager that groups entities into archetype tables.
+    ///
+    /// Joins through `query` become cache friendly at the cost of moving an
+    /// entity's components between tables whenever it gains or loses a
+    /// component type. Iteration order across tables differs from the
+    /// default layout; the rest of the API behaves the same.
+    pub fn with_archetypes() -> ComponentManager
+    {
+        ComponentManager {
+            archetypes: true,
+            ..ComponentManager::new()
+        }
+    }
+
+    /// Whether entities are grouped into archetype tables
+    pub fn uses_archetypes(&self) -> bool
+    {
+        self.archetypes
+    }
+
+    /// Choose how components of type T are stored. Types that are never
+    /// registered are `Dense`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any component of type T is stored. Once they have all been
+    /// removed the type may be registered again with another kind.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut cm = ComponentManager::with_archetypes();
+    /// cm.register::<Poisoned>(StorageKind::SparseSet);
+    /// ```
+    pub fn register<T>(&mut self, kind: StorageKind)
+        where T: 'static
+    {
+        let type_id = TypeId::of::<T>();
+        assert!(!self.tables.iter().any(|table| table.has(type_id) && table.len() > 0),
+                "storage of {} must be chosen before its first insert", type_name::<T>());
+        self.drop_empty_tables(|table| table.has(type_id));
+        self.type_tables.remove(&type_id);
+        self.kinds.insert(type_id, kind);
+    }
+
+    /// How components of the type are stored
+    pub fn storage_kind<T>(&self) -> StorageKind
+        where T: 'static
+    {
+        self.kind(TypeId::of::<T>())
+    }
+
+    fn kind(&self, type_id: TypeId) -> StorageKind
+    {
+        self.kinds.get(&type_id).cloned().unwrap_or(StorageKind::Dense)
+    }
+
+    /// Whether components of the type live in archetype tables, shared with
+    /// the entity's other such components
+    pub(crate) fn shares(&self, type_id: TypeId) -> bool
+    {
+        self.archetypes && self.kind(type_id) == StorageKind::Dense
+    }
+
+    pub(crate) fn tables(&self) -> &[Table]
+    {
+        &self.tables
+    }
+
+    pub(crate) fn changes(&self) -> &Changes
+    {
+        &self.changes
+    }
+
+    pub(crate) fn changes_mut(&mut self) -> &mut Changes
+    {
+        &mut self.changes
+    }
+
+    pub(crate) fn hooks_mut(&mut self) -> &mut Hooks
+    {
+        &mut self.hooks
+    }
+
+    pub(crate) fn cloners(&self) -> &Cloners
+    {
+        &self.cloners
+    }
+
+    pub(crate) fn cloners_mut(&mut self) -> &mut Cloners
+    {
+        &mut self.cloners
+    }
+
+    pub(crate) fn tables_mut(&mut self) -> &mut Vec<Table>
+    {
+        &mut self.tables
+    }
+
+    /// The archetype table of each entity
+    pub(crate) fn locations(&self) -> &HashMap<EntityId, usize>
+    {
+        &self.locations
+    }
+
+    /// The table holding every component of the type, for types that are
+    /// not shared
+    pub(crate) fn type_table(&self, type_id: TypeId) -> Option<usize>
+    {
+        debug_assert!(!self.shares(type_id));
+        self.type_tables.get(&type_id).and_then(|tables| tables.first()).cloned()
+    }
+
+    /// Take every column of the type out of its tables, for a parallel
+    /// processor to own while it runs. The type must not be touched
+    /// through the manager until `restore_columns` puts them back.
+    pub(crate) fn take_columns(&mut self, type_id: TypeId) -> Vec<(usize, Box<dyn AnyColumn>)>
+    {
+        let tables = self.type_tables.get(&type_id).cloned().unwrap_or_default();
+        tables.into_iter()
+            .map(|table| (table, self.tables[table].take_column(type_id).expect("table to hold the column")))
+            .collect()
+    }
+
+    pub(crate) fn restore_columns(&mut self, type_id: TypeId, columns: Vec<(usize, Box<dyn AnyColumn>)>)
+    {
+        for (table, column) in columns {
+            self.tables[table].restore_column(type_id, column);
+        }
+    }
+
+    /// Copy every table, failing if any holds a type that is not cloneable
+    pub(crate) fn clone_storage(&self) -> Result<Storage>
+    {
+        let mut tables = Vec::with_capacity(self.tables.len());
+        for table in self.tables.iter() {
+            tables.push(self.cloners.clone_table(table)?);
+        }
+        Ok(Storage {
+            tables,
+            table_index: self.table_index.clone(),
+            type_tables: self.type_tables.clone(),
+            locations: self.locations.clone(),
+        })
+    }
+
+    /// Replace every table with a copy of those in `storage`, without
+    /// telling hooks about the components that come and go
+    pub(crate) fn restore_storage(&mut self, storage: &Storage)
+    {
+        self.tables = storage.tables.iter()
+            .map(|table| self.cloners.clone_table(table).expect("snapshot to hold cloneable types"))
+            .collect();
+        self.table_index = storage.table_index.clone();
+        self.type_tables = storage.type_tables.clone();
+        self.locations = storage.locations.clone();
+    }
+
+    /// Locate the table and row holding the entity's components of type T
+    fn locate<T>(&self, id: EntityId) -> Option<(usize, usize)>
+        where T: 'static
+    {
+        self.locate_type(TypeId::of::<T>(), id)
+    }
+
+    /// Locate the table and row holding the entity's components of a type
+    /// known only by its `TypeId`
+    pub(crate) fn locate_type(&self, type_id: TypeId, id: EntityId) -> Option<(usize, usize)>
+    {
+        let table = if self.shares(type_id) {
+            *self.locations.get(&id)?
+        } else {
+            self.type_table(type_id)?
+        };
+        if !self.tables[table].has(type_id) {
+            return None;
+        }
+        Some((table, self.tables[table].row(id)?))
+    }
+
+    /// Explain why the entity has no component of type T
+    fn missing<T>(&self, id: EntityId) -> EsError
+        where T: 'static
+    {
+        self.missing_type(TypeId::of::<T>(), type_name::<T>(), id)
+    }
+
+    pub(crate) fn missing_type(&self, type_id: TypeId, type_name: &'static str, id: EntityId) -> EsError
+    {
+        if !self.type_tables.contains_key(&type_id) {
+            EsError::NoSuchComponentType(type_name)
+        } else if self.has_components(id) {
+            EsError::EntityLacksComponent(id, type_name)
+        } else {
+            EsError::NoSuchEntity(id)
+        }
+    }
+
+    fn check_contains<T>(&self) -> Result<()>
+        where T: 'static
+    {
+        if self.contains::<T>() {
+            Ok(())
+        } else {
+            Err(EsError::NoSuchComponentType(type_name::<T>()))
+        }
+    }
+
+    /// Find or create the table for a sorted set of types. `columns` builds
+    /// the empty columns of a new table.
+    fn table_for<F>(&mut self, types: Vec<TypeId>, kind: StorageKind, columns: F) -> usize
+        where F: FnOnce(&ComponentManager) -> Vec<(TypeId, Box<dyn AnyColumn>)>
+    {
+        if let Some(&table) = self.table_index.get(&types) {
+            return table;
+        }
+
+        let table = self.tables.len();
+        let columns = columns(self);
+        self.tables.push(Table::new(columns, kind));
+        for type_id in types.iter() {
+            if let Some(tables) = self.type_tables.get_mut(type_id) {
+                tables.push(table);
+            }
+        }
+        self.table_index.insert(types, table);
+        table
+    }
+
+    /// Drop the empty tables `drop` picks, renumbering the rest in every
+    /// index
+    fn drop_empty_tables<F>(&mut self, drop: F)
+        where F: Fn(&Table) -> bool
+    {
+        let mut kept = 0;
+        let renumbered: Vec<Option<usize>> = self.tables.iter()
+            .map(|table| {
+                if table.len() == 0 && drop(table) {
+                    return None;
+                }
+                kept += 1;
+                Some(kept - 1)
+            })
+            .collect();
+        if kept == self.tables.len() {
+            return;
+        }
+
+        let mut renumber = renumbered.iter();
+        self.tables.retain(|_| renumber.next().expect("a number for every table").is_some());
+        self.table_index.retain(|_, table| match renumbered[*table] {
+            Some(new) => {
+                *table = new;
+                true
+            },
+            None => false,
+        });
+        for tables in self.type_tables.values_mut() {
+            *tables = tables.iter().filter_map(|&table| renumbered[table]).collect();
+        }
+        for table in self.locations.values_mut() {
+            *table = renumbered[*table].expect("entities to live in tables that are kept");
+        }
+    }
+
+    /// Start listing the tables holding a type, as it is about to be stored
+    fn track_type(&mut self, type_id: TypeId)
+    {
+        if !self.type_tables.contains_key(&type_id) {
+            let tables = self.tables.iter()
+                .enumerate()
+                .filter(|&(_, table)| table.has(type_id))
+                .map(|(i, _)| i)
+                .collect();
+            self.type_tables.insert(type_id, tables);
+        }
+    }
+
+    /// Two distinct tables, mutably
+    fn table_pair(&mut self, a: usize, b: usize) -> (&mut Table, &mut Table)
+    {
+        assert!(a != b);
+        if a < b {
+            let (left, right) = self.tables.split_at_mut(b);
+            (&mut left[a], &mut right[0])
+        } else {
+            let (left, right) = self.tables.split_at_mut(a);
+            (&mut right[0], &mut left[b])
+        }
+    }
+
+    pub fn insert<T>(&mut self, id: EntityId, component: T)
+        where T: Component
+    {
+        let type_id = TypeId::of::<T>();
+        self.track_type(type_id);
+
+        if let Some((table, row)) = self.locate::<T>(id) {
+            self.tables[table].column_mut::<T>()
+                .expect("located table to hold T")
+                .push(row, component, self.changes.tick());
+            self.notify_inserted::<T>(id);
+            return;
+        }
+
+        let shared = self.shares(type_id);
+        let source = if shared { self.locations.get(&id).cloned() } else { None };
+        let table = match source {
+            Some(source) => {
+                let mut types = self.tables[source].types().to_vec();
+                types.push(type_id);
+                types.sort();
+                let table = self.table_for(types, StorageKind::Dense, |cm| {
+                    let mut columns = cm.tables[source].empty_columns();
+                    columns.push((type_id, Box::new(Column::<T>::new())));
+                    columns
+                });
+                let row = self.tables[source].row(id).expect("located entity to have a row");
+                let (source, destination) = self.table_pair(source, table);
+                source.move_row(row, destination);
+                table
+            },
+            None => {
+                let kind = self.kind(type_id);
+                let table = self.table_for(vec![type_id], kind, |_| {
+                    vec![(type_id, Box::new(Column::<T>::new()))]
+                });
+                self.tables[table].push_row(id);
+                table
+            },
+        };
+        self.tables[table].column_mut::<T>()
+            .expect("table to hold T")
+            .push_row(id, component, self.changes.tick());
+        if shared {
+            self.locations.insert(id, table);
+        }
+        self.notify_inserted::<T>(id);
+    }
+
+    /// Insert several components at once.
+    ///
+    /// With archetypes, an entity without components goes straight into the
+    /// table for the bundle's types rather than moving through a table for
+    /// each insert.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// cm.insert_bundle(entity, (Position{x: 0, y: 0}, Velocity{x: 1, y: 0}));
+    /// ```
+    pub fn insert_bundle<B>(&mut self, id: EntityId, bundle: B)
+        where B: Bundle
+    {
+        let mut types = B::types();
+        types.sort();
+        types.dedup();
+        let direct = types.len() == B::types().len()
+            && types.iter().all(|&type_id| self.shares(type_id))
+            && !self.locations.contains_key(&id);
+        if !direct {
+            bundle.insert(self, id);
+            return;
+        }
+
+        for &type_id in types.iter() {
+            self.track_type(type_id);
+        }
+        let table = self.table_for(types, StorageKind::Dense, |_| B::columns());
+        bundle.push_row(&mut self.tables[table], id, self.changes.tick());
+        self.locations.insert(id, table);
+        B::notify_inserted(self, id);
+    }
+
+    /// Pass the component of type T just inserted into an entity to any
+    /// hooks observing the type
+    pub(crate) fn notify_inserted<T>(&mut self, id: EntityId)
+        where T: 'static
+    {
+        let type_id = TypeId::of::<T>();
+        if !self.hooks.observes(type_id) {
+            return;
+        }
+        let (table, row) = self.locate::<T>(id).expect("inserted component to be located");
+        let component = self.tables[table].column::<T>()
+            .expect("located table to hold T")
+            .iter_row(row)
+            .last()
+            .expect("row to hold the inserted component");
+        self.hooks.inserted(type_id, id, component);
+    }
+
+    pub fn find<T>(&self) -> Vec<EntityMeta<T>>
+        where T: Clone+'static
+    {
+        self.try_find().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_find<T>(&self) -> Result<Vec<EntityMeta<T>>>
+        where T: Clone+'static
+    {
+        self.check_contains::<T>()?;
+        Ok(self.iter::<T>()
+            .cloned()
+            .collect())
+    }
+
+    pub fn find_mut<T>(&mut self) -> Vec<Mut<'_, EntityMeta<T>>>
+        where T: 'static
+    {
+        self.try_find_mut().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_find_mut<T>(&mut self) -> Result<Vec<Mut<'_, EntityMeta<T>>>>
+        where T: 'static
+    {
+        self.check_contains::<T>()?;
+        Ok(self.iter_mut::<T>()
+            .collect())
+    }
+
+    /// Iterate over every component of type T without cloning or allocating.
+    ///
+    /// Yields nothing if no component of type T exists.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// for meta in cm.iter::<Position>() {
+    ///     println!("{} is at {}", meta.entity, meta.component.x);
+    /// }
+    /// ```
+    pub fn iter<T>(&self) -> Iter<'_, T>
+        where T: 'static
+    {
+        Iter::new(&self.tables)
+    }
+
+    /// Like `iter`, but mutable. Each component is yielded behind a `Mut`
+    /// guard, marking it changed only once written.
+    pub fn iter_mut<T>(&mut self) -> IterMut<'_, T>
+        where T: 'static
+    {
+        IterMut::new(&mut self.tables, self.changes.tick())
+    }
+
+    /// Iterate over an entity's components of type T without cloning or
+    /// allocating.
+    ///
+    /// Yields nothing if the entity has no component of type T.
+    pub fn iter_for<T>(&self, id: EntityId) -> IterFor<'_, T>
+        where T: 'static
+    {
+        match self.locate::<T>(id) {
+            Some((table, row)) => self.tables[table].column::<T>()
+                .expect("located table to hold T")
+                .iter_row(row),
+            None => IterFor::empty(),
+        }
+    }
+
+    /// Like `iter_for`, but mutable, yielding `Mut` guards as `iter_mut`
+    /// does.
+    pub fn iter_for_mut<T>(&mut self, id: EntityId) -> IterForMut<'_, T>
+        where T: 'static
+    {
+        match self.locate::<T>(id) {
+            Some((table, row)) => self.tables[table].column_mut::<T>()
+                .expect("located table to hold T")
+                .iter_row_mut(row, self.changes.tick()),
+            None => IterForMut::empty(),
+        }
+    }
+
+    pub fn contains<T>(&self) -> bool
+        where T: 'static
+    {
+        self.type_tables.contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn remove<T>(&mut self) -> bool
+        where T: 'static
+    {
+        let tables = match self.type_tables.get(&TypeId::of::<T>()) {
+            Some(tables) => tables.clone(),
+            None => return false,
+        };
+
+        if self.shares(TypeId::of::<T>()) {
+            let entities: Vec<EntityId> = tables.iter()
+                .flat_map(|&table| self.tables[table].entities().to_vec())
+                .collect();
+            for id in entities {
+                self.remove_for::<T>(id);
+            }
+        } else {
+            let type_id = TypeId::of::<T>();
+            for table in tables {
+                for (row, &id) in self.tables[table].entities().iter().enumerate() {
+                    self.changes.record_removed(type_id, id);
+                    if self.hooks.observes(type_id) {
+                        for component in self.tables[table].row_components(type_id, row) {
+                            self.hooks.removed(type_id, id, component);
+                        }
+                    }
+                }
+                self.tables[table].clear();
+            }
+        }
+        self.type_tables.remove(&TypeId::of::<T>());
+        true
+    }
+
+    /// Remove every component of type T from a single entity, returning the
+    /// removed components in insertion order.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let cured = cm.remove_for::<Poisoned>(entity);
+    /// ```
+    pub fn remove_for<T>(&mut self, id: EntityId) -> Vec<T>
+        where T: 'static
+    {
+        let type_id = TypeId::of::<T>();
+        let removed = self.take_for::<T>(id);
+        if self.hooks.observes(type_id) {
+            for component in removed.iter() {
+                self.hooks.removed(type_id, id, component);
+            }
+        }
+        removed
+    }
+
+    fn take_for<T>(&mut self, id: EntityId) -> Vec<T>
+        where T: 'static
+    {
+        let (table, row) = match self.locate::<T>(id) {
+            Some(location) => location,
+            None => return Vec::new(),
+        };
+        let type_id = TypeId::of::<T>();
+        self.changes.record_removed(type_id, id);
+        if !self.shares(type_id) {
+            return self.tables[table].take_row::<T>(row, None).0;
+        }
+
+        let types: Vec<TypeId> = self.tables[table].types().iter()
+            .cloned()
+            .filter(|&t| t != type_id)
+            .collect();
+        if types.is_empty() {
+            self.locations.remove(&id);
+            return self.tables[table].take_row::<T>(row, None).0;
+        }
+
+        let destination = self.table_for(types, StorageKind::Dense, |cm| {
+            cm.tables[table].empty_columns()
+                .into_iter()
+                .filter(|&(t, _)| t != type_id)
+                .collect()
+        });
+        self.locations.insert(id, destination);
+        let (source, destination) = self.table_pair(table, destination);
+        source.take_row::<T>(row, Some(destination)).0
+    }
+
+    /// Remove a single component of type T from an entity. `index` is the
+    /// component's position in the results of `find_for`.
+    ///
+    /// Returns None if the entity has no such component.
+    pub fn remove_one<T>(&mut self, id: EntityId, index: usize) -> Option<T>
+        where T: 'static
+    {
+        let (table, row) = self.locate::<T>(id)?;
+        let count = self.tables[table].column::<T>()?.count(row);
+        if index >= count {
+            None
+        } else if count > 1 {
+            let component = self.tables[table].column_mut::<T>()?.remove_one(row, index);
+            self.changes.record_removed(TypeId::of::<T>(), id);
+            self.hooks.removed(TypeId::of::<T>(), id, &component);
+            Some(component)
+        } else {
+            self.remove_for::<T>(id).pop()
+        }
+    }
+
+    /// Remove every component belonging to an entity, typically one that
+    /// has just been destroyed through `EntityManager::destroy`.
+    ///
+    /// Returns false if the entity had no components. Any parent or children
+    /// the entity had in the hierarchy are detached from it.
+    pub fn purge(&mut self, id: EntityId) -> bool
+    {
+        self.detach(id);
+        self.locations.remove(&id);
+        let mut removed = false;
+        for table in self.tables.iter_mut() {
+            if let Some(row) = table.row(id) {
+                for &type_id in table.types() {
+                    self.changes.record_removed(type_id, id);
+                    if self.hooks.observes(type_id) {
+                        for component in table.row_components(type_id, row) {
+                            self.hooks.removed(type_id, id, component);
+                        }
+                    }
+                }
+                table.swap_remove_row(row);
+                removed = true;
+            }
+        }
+        removed
+    }
+
+    pub fn find_for<T>(&self, id:EntityId) -> Vec<T>
+        where T: Clone+'static
+    {
+        self.try_find_for(id).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_find_for<T>(&self, id:EntityId) -> Result<Vec<T>>
+        where T: Clone+'static
+    {
+        self.check_contains::<T>()?;
+        if self.locate::<T>(id).is_none() {
+            return Err(self.missing::<T>(id));
+        }
+        Ok(self.iter_for(id)
+            .cloned()
+            .collect())
+    }
+
+    pub fn find_for_mut<T>(&mut self, id:EntityId) -> Vec<Mut<'_, T>>
+        where T: 'static
+    {
+        self.try_find_for_mut(id).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_find_for_mut<T>(&mut self, id:EntityId) -> Result<Vec<Mut<'_, T>>>
+        where T: 'static
+    {
+        self.check_contains::<T>()?;
+        if self.locate::<T>(id).is_none() {
+            return Err(self.missing::<T>(id));
+        }
+        Ok(self.iter_for_mut(id)
+            .collect())
+    }
+
+    pub fn get<T>(&self, id:EntityId) -> T
+        where T: Clone+'static
+    {
+        self.try_get(id).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_get<T>(&self, id:EntityId) -> Result<T>
+        where T: Clone+'static
+    {
+        self.check_contains::<T>()?;
+        match self.locate::<T>(id) {
+            Some((table, row)) => Ok(self.tables[table].column::<T>()
+                .expect("located table to hold T")
+                .get(row)
+                .clone()),
+            None => Err(self.missing::<T>(id)),
+        }
+    }
+
+    /// Mutable access to the entity's first component of type T, through a
+    /// guard that marks it changed once written.
+    pub fn get_mut<T>(&mut self, id:EntityId) -> Mut<'_, T>
+        where T: Clone+'static
+    {
+        self.try_get_mut(id).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_get_mut<T>(&mut self, id:EntityId) -> Result<Mut<'_, T>>
+        where T: Clone+'static
+    {
+        self.check_contains::<T>()?;
+        match self.locate::<T>(id) {
+            Some((table, row)) => Ok(self.tables[table].column_mut::<T>()
+                .expect("located table to hold T")
+                .get_mut(row, self.changes.tick())),
+            None => Err(self.missing::<T>(id)),
+        }
+    }
+
+    /// Whether the entity holds a component of type T
+    pub fn has<T>(&self, id: EntityId) -> bool
+        where T: 'static
+    {
+        self.locate::<T>(id).is_some()
+    }
+
+    /// The number of components of type T the entity holds
+    pub fn count<T>(&self, id: EntityId) -> usize
+        where T: 'static
+    {
+        self.iter_for::<T>(id).count()
+    }
+
+    /// Whether the entity exists: it is alive in `em`, the manager that
+    /// created it. A live entity need not hold any component, so the
+    /// inspector can list it before anything is attached.
+    pub fn entity_exists(&self, em: &EntityManager, id: EntityId) -> bool
+    {
+        em.is_alive(id)
+    }
+
+    /// Whether the entity holds any component at all. Whether it is alive
+    /// is up to its `EntityManager`, see `entity_exists`.
+    pub fn has_components(&self, id: EntityId) -> bool
+    {
+        self.tables.iter().any(|table| table.row(id).is_some())
+    }
+
+    /// Every type of component the entity holds, with the names they are
+    /// registered under in `registry`. Sorted by registered name, or by Rust
+    /// name for types that are not registered.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// for component_type in cm.component_types_of(selected, &registry) {
+    ///     inspector.add_row(component_type.name.unwrap_or(component_type.type_name));
+    /// }
+    /// ```
+    pub fn component_types_of<'a>(&self, id: EntityId, registry: &'a Registry) -> Vec<ComponentType<'a>>
+    {
+        let mut types: Vec<ComponentType> = self.tables.iter()
+            .filter(|table| table.row(id).is_some())
+            .flat_map(|table| table.types().iter().map(move |&type_id| ComponentType {
+                type_id,
+                type_name: table.type_name(type_id).expect("table to hold a column for each type"),
+                name: registry.name_of_type(type_id),
+            }))
+            .collect();
+        types.sort_by_key(|component_type| component_type.name.unwrap_or(component_type.type_name));
+        types
+    }
+
+    pub fn find_entities_for_type<T>(&self) -> Vec<EntityId>
+        where T: 'static
+    {
+        match self.type_tables.get(&TypeId::of::<T>()) {
+            Some(tables) => tables.iter()
+                .flat_map(|&table| self.tables[table].entities().iter().cloned())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Join several component types over the entities that have all of them.
+    ///
+    /// Each item is the entity id followed by one reference per requested
+    /// type, or a `Mut<T>` guard for `&mut T`. `Option<&T>` and
+    /// `Option<&mut T>` fetch a component when the entity has one without
+    /// requiring it. At least one type must be requested without `Option`;
+    /// a query of nothing but `Option`s fails to compile.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a component type is requested mutably alongside any other
+    /// request for the same type.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// for (entity, position, mut velocity) in cm.query::<(&Position, &mut Velocity)>() {
+    ///     velocity.x -= position.x;
+    /// }
+    /// ```
+    pub fn query<'a, Q>(&'a mut self) -> QueryIter<'a, Q, ()>
+        where Q: Query<'a>
+    {
+        QueryIter::new(self)
+    }
+
+    /// Like `query`, additionally restricted by `With<T>` / `Without<T>`
+    /// filters. Filters do not count towards the type `query` requires
+    /// without `Option`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// for (entity, mut velocity) in cm.query_filtered::<(&mut Velocity,), Without<Frozen>>() {
+    ///     velocity.x += 1;
+    /// }
+    /// ```
+    pub fn query_filtered<'a, Q, F>(&'a mut self) -> QueryIter<'a, Q, F>
+        where Q: Query<'a>, F: Filter
+    {
+        QueryIter::new(self)
+    }
+}
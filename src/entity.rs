@@ -0,0 +1,293 @@
+use std::collections::hash_map::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use error::{EsError, Result};
+
+/// A unique identifier for a game object.
+///
+/// The low 32 bits hold the slot index and the high 32 bits hold the slot's
+/// generation. Destroying an entity bumps the generation of its slot, so an
+/// id kept around after its entity was destroyed never matches the entity
+/// that later reuses the slot.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct EntityId(u64);
+
+impl EntityId {
+    pub fn new(index: u32, generation: u32) -> EntityId
+    {
+        EntityId(((generation as u64) << 32) | index as u64)
+    }
+
+    pub fn index(&self) -> u32
+    {
+        self.0 as u32
+    }
+
+    pub fn generation(&self) -> u32
+    {
+        (self.0 >> 32) as u32
+    }
+
+    /// The packed representation of this id
+    pub fn to_bits(&self) -> u64
+    {
+        self.0
+    }
+
+    pub fn from_bits(bits: u64) -> EntityId
+    {
+        EntityId(bits)
+    }
+}
+
+impl fmt::Debug for EntityId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "EntityId({}v{})", self.index(), self.generation())
+    }
+}
+
+impl fmt::Display for EntityId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{}v{}", self.index(), self.generation())
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Slot {
+    generation: u32,
+    alive: bool,
+}
+
+/// What naming an entity does when another entity already has the name
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NamePolicy {
+    /// Refuse with `EsError::DuplicateName`
+    Error,
+    /// Take the name away from the entity holding it
+    Overwrite,
+}
+
+/// Creates unique entities along and keeps tracked of named entities
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "SavedEntities")]
+pub struct EntityManager {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    named_entities: HashMap<String, EntityId>,
+    #[serde(skip_serializing)]
+    entity_names: HashMap<EntityId, String>,
+    #[serde(skip_serializing)]
+    name_policy: NamePolicy,
+}
+
+/// The saved fields of an `EntityManager`
+#[derive(Deserialize)]
+struct SavedEntities {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    named_entities: HashMap<String, EntityId>,
+}
+
+impl From<SavedEntities> for EntityManager {
+    fn from(saved: SavedEntities) -> EntityManager
+    {
+        EntityManager {
+            entity_names: saved.named_entities.iter()
+                .map(|(name, &id)| (id, name.clone()))
+                .collect(),
+            slots: saved.slots,
+            free: saved.free,
+            named_entities: saved.named_entities,
+            name_policy: NamePolicy::Error,
+        }
+    }
+}
+
+impl Default for EntityManager {
+    fn default() -> EntityManager
+    {
+        EntityManager::new()
+    }
+}
+
+impl EntityManager {
+    pub fn new() -> EntityManager
+    {
+        EntityManager {
+            slots: Vec::new(),
+            free: Vec::new(),
+            named_entities: HashMap::new(),
+            entity_names: HashMap::new(),
+            name_policy: NamePolicy::Error,
+        }
+    }
+
+    /// Choose what naming an entity does when the name is taken. Defaults to
+    /// `NamePolicy::Error`.
+    pub fn set_name_policy(&mut self, policy: NamePolicy)
+    {
+        self.name_policy = policy;
+    }
+
+    pub fn name_policy(&self) -> NamePolicy
+    {
+        self.name_policy
+    }
+
+    /// Generate a unique entity
+    ///
+    /// Slots freed by `destroy` are reused under a new generation.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut em = EntityManager::new();
+    /// let entity = em.create();
+    /// ```
+    pub fn create(&mut self) -> EntityId
+    {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.alive = true;
+                EntityId::new(index, slot.generation)
+            },
+            None => {
+                let index = self.slots.len() as u32;
+                self.slots.push(Slot{generation: 0, alive: true});
+                EntityId::new(index, 0)
+            },
+        }
+    }
+
+    /// Generate a unique entity with a name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name is taken and the name policy is
+    /// `NamePolicy::Error`.
+    pub fn create_named<N>(&mut self, name: N) -> EntityId
+        where N: Into<String>
+    {
+        self.try_create_named(name).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_create_named<N>(&mut self, name: N) -> Result<EntityId>
+        where N: Into<String>
+    {
+        let name = name.into();
+        self.check_name(&name, None)?;
+        let id = self.create();
+        self.set_name(id, name);
+        Ok(id)
+    }
+
+    pub fn get_named(&self, name: &str) -> Result<EntityId>
+    {
+        match self.named_entities.get(name) {
+            Some(entity) => Ok(*entity),
+            None => Err(EsError::NoSuchNamedEntity(name.to_string())),
+        }
+    }
+
+    /// The entity's name, if it has one
+    pub fn name_of(&self, id: EntityId) -> Option<&str>
+    {
+        self.entity_names.get(&id).map(|name| &**name)
+    }
+
+    /// Give a living entity a name, replacing any name it had.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let entity = em.create();
+    /// em.rename(entity, format!("goblin-{}", level.goblins))?;
+    /// ```
+    pub fn rename<N>(&mut self, id: EntityId, name: N) -> Result<()>
+        where N: Into<String>
+    {
+        if !self.is_alive(id) {
+            return Err(EsError::NoSuchEntity(id));
+        }
+        let name = name.into();
+        self.check_name(&name, Some(id))?;
+        self.unname(id);
+        self.set_name(id, name);
+        Ok(())
+    }
+
+    /// Remove the entity's name, returning it
+    pub fn unname(&mut self, id: EntityId) -> Option<String>
+    {
+        let name = self.entity_names.remove(&id)?;
+        self.named_entities.remove(&name);
+        Some(name)
+    }
+
+    /// Iterate over every name along with the entity holding it
+    pub fn names(&self) -> impl Iterator<Item = (&str, EntityId)>
+    {
+        self.named_entities.iter().map(|(name, &id)| (&**name, id))
+    }
+
+    /// Fails if the name belongs to an entity other than `id` and the policy
+    /// forbids taking it
+    fn check_name(&self, name: &str, id: Option<EntityId>) -> Result<()>
+    {
+        match self.named_entities.get(name) {
+            Some(&holder) if Some(holder) != id && self.name_policy == NamePolicy::Error =>
+                Err(EsError::DuplicateName(name.to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    fn set_name(&mut self, id: EntityId, name: String)
+    {
+        if let Some(holder) = self.named_entities.insert(name.clone(), id) {
+            self.entity_names.remove(&holder);
+        }
+        self.entity_names.insert(id, name);
+    }
+
+    /// Destroy an entity, freeing its slot for reuse and forgetting its name.
+    ///
+    /// Returns false if the entity was not alive. The entity's components are
+    /// not touched; follow up with `ComponentManager::purge`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let entity = em.create();
+    /// em.destroy(entity);
+    /// cm.purge(entity);
+    /// assert!(!em.is_alive(entity));
+    /// ```
+    pub fn destroy(&mut self, id: EntityId) -> bool
+    {
+        if !self.is_alive(id) {
+            return false;
+        }
+
+        let slot = &mut self.slots[id.index() as usize];
+        slot.alive = false;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(id.index());
+        self.unname(id);
+        true
+    }
+
+    /// Whether the id refers to an entity that has been created and not yet
+    /// destroyed
+    pub fn is_alive(&self, id: EntityId) -> bool
+    {
+        match self.slots.get(id.index() as usize) {
+            Some(slot) => slot.alive && slot.generation == id.generation(),
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::error::Error;
+use std::fmt;
+use std::result;
+
+use entity::EntityId;
+
+/// Errors returned by the fallible lookups on `EntityManager` and
+/// `ComponentManager`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EsError {
+    /// No entity is known by the given id
+    NoSuchEntity(EntityId),
+    /// No entity is registered under the given name
+    NoSuchNamedEntity(String),
+    /// Another entity already has the name
+    DuplicateName(String),
+    /// No component of the named type has been inserted
+    NoSuchComponentType(&'static str),
+    /// The entity exists but has no component of the named type
+    EntityLacksComponent(EntityId, &'static str),
+    /// Parenting the first entity to the second would make it its own
+    /// ancestor
+    HierarchyCycle(EntityId, EntityId),
+    /// No resource of the named type has been inserted
+    NoSuchResource(&'static str),
+    /// A snapshot was taken while components of the named type, which is
+    /// not registered as cloneable, exist
+    NotCloneable(&'static str),
+    /// The named component type has no field of the given name
+    NoSuchField(String, String),
+    /// A value written to a field of the named component type does not fit
+    /// it
+    InvalidFieldValue(String, String, String),
+    /// No prefab is known by the given name
+    NoSuchPrefab(String),
+    /// A save refers to a component type missing from the `Registry`
+    UnregisteredComponentType(String),
+    /// A save could not be written or read
+    Serialization(String),
+}
+
+pub type Result<T> = result::Result<T, EsError>;
+
+impl fmt::Display for EsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self {
+            EsError::NoSuchEntity(id) =>
+                write!(f, "Could not find entity: {}", id),
+            EsError::NoSuchNamedEntity(ref name) =>
+                write!(f, "Could not find named entity: {}", name),
+            EsError::DuplicateName(ref name) =>
+                write!(f, "Entity name already in use: {}", name),
+            EsError::NoSuchComponentType(type_name) =>
+                write!(f, "No components of type {} exist", type_name),
+            EsError::EntityLacksComponent(id, type_name) =>
+                write!(f, "Entity {} has no component of type {}", id, type_name),
+            EsError::HierarchyCycle(child, parent) =>
+                write!(f, "Entity {} cannot be parented to its descendant {}", child, parent),
+            EsError::NoSuchResource(type_name) =>
+                write!(f, "No resource of type {} exists", type_name),
+            EsError::NotCloneable(type_name) =>
+                write!(f, "Components of type {} are not registered as cloneable", type_name),
+            EsError::NoSuchField(ref name, ref field) =>
+                write!(f, "Component type {} has no field {}", name, field),
+            EsError::InvalidFieldValue(ref name, ref field, ref message) =>
+                write!(f, "Invalid value for field {} of component type {}: {}", field, name, message),
+            EsError::NoSuchPrefab(ref name) =>
+                write!(f, "Could not find prefab: {}", name),
+            EsError::UnregisteredComponentType(ref name) =>
+                write!(f, "No component type is registered as: {}", name),
+            EsError::Serialization(ref message) =>
+                write!(f, "Could not serialize world: {}", message),
+        }
+    }
+}
+
+impl Error for EsError {}
@@ -0,0 +1,237 @@
+use std::any::{Any, TypeId};
+use std::collections::hash_map::HashMap;
+use std::collections::VecDeque;
+use std::collections::vec_deque;
+use std::marker::PhantomData;
+
+/// A registered reader of events of type E, see `EventBus::reader`.
+///
+/// Each reader sees every event sent after it was created, independently of
+/// any other reader.
+pub struct EventReader<E> {
+    id: usize,
+    marker: PhantomData<E>,
+}
+
+/// The events of one type, numbered in the order they were sent
+struct Channel<E> {
+    /// Events paired with the tick they were sent on, oldest first
+    events: VecDeque<(u64, E)>,
+    /// The number of the oldest event still held
+    first: u64,
+    /// The number of the next unread event for each reader, None once the
+    /// reader is removed
+    cursors: Vec<Option<u64>>,
+}
+
+impl<E> Channel<E> {
+    fn new() -> Channel<E>
+    {
+        Channel {
+            events: VecDeque::new(),
+            first: 0,
+            cursors: Vec::new(),
+        }
+    }
+
+    fn end(&self) -> u64
+    {
+        self.first + self.events.len() as u64
+    }
+}
+
+trait AnyChannel {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    /// Drop the events every reader has read, and any sent more than
+    /// `max_age` ticks before `tick`
+    fn clean(&mut self, tick: u64, max_age: Option<u64>);
+}
+
+impl<E> AnyChannel for Channel<E>
+    where E: 'static
+{
+    fn as_any(&self) -> &dyn Any
+    {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any
+    {
+        self
+    }
+
+    fn clean(&mut self, tick: u64, max_age: Option<u64>)
+    {
+        let read = self.cursors.iter()
+            .filter_map(|&cursor| cursor)
+            .min()
+            .unwrap_or_else(|| self.end());
+        while let Some(&(sent, _)) = self.events.front() {
+            let expired = max_age.is_some_and(|max_age| tick - sent >= max_age);
+            if self.first >= read && !expired {
+                break;
+            }
+            self.events.pop_front();
+            self.first += 1;
+        }
+    }
+}
+
+/// Transient events passed between processors, kept in one channel per
+/// event type.
+///
+/// An event is dropped by `update` once every reader of its type has read
+/// it, or once it is older than the maximum age if one is set. Events sent
+/// while a type has no readers are dropped by the next `update`. Readers
+/// that fall behind an expired event never see it.
+///
+/// The bus of a `World` is kept beside its `ComponentManager`, handed to
+/// each processor and updated after every tick.
+///
+/// # Example
+///
+/// ```rust
+/// let mut damage = world.events_mut().reader::<Damage>();
+///
+/// events.send(Damage{entity, amount: 5});
+/// for event in events.read(&mut damage) {
+///     cm.get_mut::<Health>(event.entity).hp -= event.amount;
+/// }
+/// ```
+pub struct EventBus {
+    channels: HashMap<TypeId, Box<dyn AnyChannel>>,
+    tick: u64,
+    max_age: Option<u64>,
+}
+
+impl Default for EventBus {
+    fn default() -> EventBus
+    {
+        EventBus::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> EventBus
+    {
+        EventBus {
+            channels: HashMap::new(),
+            tick: 0,
+            max_age: None,
+        }
+    }
+
+    /// Drop events once they are `max_age` updates old, read or not. None,
+    /// the default, keeps events until every reader has read them.
+    pub fn set_max_age(&mut self, max_age: Option<u64>)
+    {
+        self.max_age = max_age;
+    }
+
+    pub fn max_age(&self) -> Option<u64>
+    {
+        self.max_age
+    }
+
+    fn channel<E>(&self) -> Option<&Channel<E>>
+        where E: 'static
+    {
+        self.channels.get(&TypeId::of::<E>())
+            .map(|channel| channel.as_any().downcast_ref::<Channel<E>>()
+                .expect("downcast to Channel<E>"))
+    }
+
+    fn channel_mut<E>(&mut self) -> &mut Channel<E>
+        where E: 'static
+    {
+        self.channels.entry(TypeId::of::<E>())
+            .or_insert_with(|| Box::new(Channel::<E>::new()))
+            .as_any_mut()
+            .downcast_mut::<Channel<E>>()
+            .expect("downcast to Channel<E>")
+    }
+
+    pub fn send<E>(&mut self, event: E)
+        where E: 'static
+    {
+        let tick = self.tick;
+        self.channel_mut::<E>().events.push_back((tick, event));
+    }
+
+    /// Register a reader of events of type E. It sees the events sent from
+    /// now on.
+    pub fn reader<E>(&mut self) -> EventReader<E>
+        where E: 'static
+    {
+        let channel = self.channel_mut::<E>();
+        let cursor = Some(channel.end());
+        let id = match channel.cursors.iter().position(Option::is_none) {
+            Some(id) => {
+                channel.cursors[id] = cursor;
+                id
+            },
+            None => {
+                channel.cursors.push(cursor);
+                channel.cursors.len() - 1
+            },
+        };
+        EventReader{id, marker: PhantomData}
+    }
+
+    /// Stop tracking a reader, so events no longer wait for it to read them
+    pub fn remove_reader<E>(&mut self, reader: EventReader<E>)
+        where E: 'static
+    {
+        self.channel_mut::<E>().cursors[reader.id] = None;
+    }
+
+    /// Iterate over the events the reader has not yet read, oldest first,
+    /// marking them read
+    pub fn read<E>(&mut self, reader: &mut EventReader<E>) -> EventIter<'_, E>
+        where E: 'static
+    {
+        let channel = self.channel_mut::<E>();
+        let cursor = channel.cursors[reader.id].expect("event reader to be registered");
+        let start = cursor.saturating_sub(channel.first) as usize;
+        channel.cursors[reader.id] = Some(channel.end());
+        EventIter{inner: channel.events.range(start..)}
+    }
+
+    /// The number of events the reader has not yet read
+    pub fn unread<E>(&self, reader: &EventReader<E>) -> usize
+        where E: 'static
+    {
+        let channel = match self.channel::<E>() {
+            Some(channel) => channel,
+            None => return 0,
+        };
+        let cursor = channel.cursors[reader.id].expect("event reader to be registered");
+        (channel.end() - cursor.max(channel.first)) as usize
+    }
+
+    /// Advance the bus by one tick, dropping events that have been read by
+    /// every reader or have expired
+    pub fn update(&mut self)
+    {
+        self.tick += 1;
+        for channel in self.channels.values_mut() {
+            channel.clean(self.tick, self.max_age);
+        }
+    }
+}
+
+/// Iterator over unread events, see `EventBus::read`
+pub struct EventIter<'a, E: 'a> {
+    inner: vec_deque::Iter<'a, (u64, E)>,
+}
+
+impl<'a, E> Iterator for EventIter<'a, E> {
+    type Item = &'a E;
+
+    fn next(&mut self) -> Option<&'a E>
+    {
+        self.inner.next().map(|(_, event)| event)
+    }
+}
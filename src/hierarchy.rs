@@ -0,0 +1,161 @@
+use std::iter;
+
+use serde::{Deserialize, Serialize};
+
+use component::ComponentManager;
+use entity::{EntityId, EntityManager};
+use error::{EsError, Result};
+
+/// The parent of an entity, see `ComponentManager::set_parent`.
+///
+/// Only the hierarchy methods create or change it, keeping it in step with
+/// the parent's `Children`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Parent(EntityId);
+
+impl Parent {
+    pub fn get(&self) -> EntityId
+    {
+        self.0
+    }
+}
+
+/// The children of an entity, in the order they were attached
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Children(Vec<EntityId>);
+
+impl Children {
+    pub fn as_slice(&self) -> &[EntityId]
+    {
+        &self.0
+    }
+}
+
+/// Iterator over an entity's parent, grandparent and so on, see
+/// `ComponentManager::ancestors`
+pub struct Ancestors<'a> {
+    cm: &'a ComponentManager,
+    next: Option<EntityId>,
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = EntityId;
+
+    fn next(&mut self) -> Option<EntityId>
+    {
+        let id = self.next?;
+        self.next = self.cm.parent_of(id);
+        Some(id)
+    }
+}
+
+/// Depth-first iterator over an entity's descendants, see
+/// `ComponentManager::descendants`
+pub struct Descendants<'a> {
+    cm: &'a ComponentManager,
+    stack: Vec<EntityId>,
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = EntityId;
+
+    fn next(&mut self) -> Option<EntityId>
+    {
+        let id = self.stack.pop()?;
+        self.stack.extend(self.cm.children_of(id).iter().rev());
+        Some(id)
+    }
+}
+
+impl ComponentManager {
+    /// Attach `child` to `parent`, detaching it from any previous parent.
+    ///
+    /// Fails with `EsError::HierarchyCycle` if `parent` is `child` or one of
+    /// its descendants.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// cm.set_parent(sword, player)?;
+    /// assert_eq!(cm.children_of(player), &[sword]);
+    /// ```
+    pub fn set_parent(&mut self, child: EntityId, parent: EntityId) -> Result<()>
+    {
+        if iter::once(parent).chain(self.ancestors(parent)).any(|id| id == child) {
+            return Err(EsError::HierarchyCycle(child, parent));
+        }
+
+        self.remove_parent(child);
+        self.insert(child, Parent(parent));
+        match self.iter_for_mut::<Children>(parent).next() {
+            Some(mut children) => children.0.push(child),
+            None => self.insert(parent, Children(vec![child])),
+        }
+        Ok(())
+    }
+
+    /// Detach an entity from its parent, returning the parent
+    pub fn remove_parent(&mut self, child: EntityId) -> Option<EntityId>
+    {
+        let parent = self.remove_for::<Parent>(child).pop()?.0;
+        let empty = match self.iter_for_mut::<Children>(parent).next() {
+            Some(mut children) => {
+                children.0.retain(|&id| id != child);
+                children.0.is_empty()
+            },
+            None => false,
+        };
+        if empty {
+            self.remove_for::<Children>(parent);
+        }
+        Some(parent)
+    }
+
+    pub fn parent_of(&self, id: EntityId) -> Option<EntityId>
+    {
+        self.iter_for::<Parent>(id).next().map(Parent::get)
+    }
+
+    pub fn children_of(&self, id: EntityId) -> &[EntityId]
+    {
+        self.iter_for::<Children>(id).next().map_or(&[], Children::as_slice)
+    }
+
+    /// Iterate from the entity's parent up to the root of its tree
+    pub fn ancestors(&self, id: EntityId) -> Ancestors<'_>
+    {
+        Ancestors{cm: self, next: self.parent_of(id)}
+    }
+
+    /// Iterate over the entity's descendants depth-first, each entity before
+    /// its children
+    pub fn descendants(&self, id: EntityId) -> Descendants<'_>
+    {
+        Descendants{cm: self, stack: self.children_of(id).iter().rev().cloned().collect()}
+    }
+
+    /// Destroy an entity along with all of its descendants, purging their
+    /// components. Returns the destroyed entities, depth-first.
+    pub fn destroy_subtree(&mut self, em: &mut EntityManager, root: EntityId) -> Vec<EntityId>
+    {
+        let subtree: Vec<EntityId> = iter::once(root).chain(self.descendants(root)).collect();
+        for &id in subtree.iter() {
+            em.destroy(id);
+            self.purge(id);
+        }
+        subtree
+    }
+
+    /// Drop the entity from its parent's children and orphan its own
+    /// children, ahead of purging it
+    pub(crate) fn detach(&mut self, id: EntityId)
+    {
+        if !self.contains::<Parent>() {
+            return;
+        }
+        self.remove_parent(id);
+        for child in self.children_of(id).to_vec() {
+            self.remove_for::<Parent>(child);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::any::{Any, TypeId};
+use std::collections::hash_map::HashMap;
+use std::collections::HashSet;
+use std::vec;
+
+use component::ComponentManager;
+use entity::EntityId;
+
+/// Whether a component was inserted or removed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComponentEventKind {
+    Inserted,
+    Removed,
+}
+
+/// A component inserted into or removed from an entity, see
+/// `ComponentManager::queue_events`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ComponentEvent {
+    pub entity: EntityId,
+    pub type_id: TypeId,
+    pub kind: ComponentEventKind,
+}
+
+type Hook = Box<dyn FnMut(EntityId, &dyn Any)>;
+
+/// The callbacks and queued events for observed component types
+pub(crate) struct Hooks {
+    on_insert: HashMap<TypeId, Vec<Hook>>,
+    on_remove: HashMap<TypeId, Vec<Hook>>,
+    queued: HashSet<TypeId>,
+    events: Vec<ComponentEvent>,
+}
+
+impl Hooks {
+    pub fn new() -> Hooks
+    {
+        Hooks {
+            on_insert: HashMap::new(),
+            on_remove: HashMap::new(),
+            queued: HashSet::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Whether anything needs to hear about components of this type
+    pub fn observes(&self, type_id: TypeId) -> bool
+    {
+        self.on_insert.contains_key(&type_id)
+            || self.on_remove.contains_key(&type_id)
+            || self.queued.contains(&type_id)
+    }
+
+    pub fn inserted(&mut self, type_id: TypeId, id: EntityId, component: &dyn Any)
+    {
+        self.notify(ComponentEventKind::Inserted, type_id, id, component);
+    }
+
+    pub fn removed(&mut self, type_id: TypeId, id: EntityId, component: &dyn Any)
+    {
+        self.notify(ComponentEventKind::Removed, type_id, id, component);
+    }
+
+    fn notify(&mut self, kind: ComponentEventKind, type_id: TypeId, id: EntityId, component: &dyn Any)
+    {
+        let hooks = match kind {
+            ComponentEventKind::Inserted => &mut self.on_insert,
+            ComponentEventKind::Removed => &mut self.on_remove,
+        };
+        if let Some(hooks) = hooks.get_mut(&type_id) {
+            for hook in hooks.iter_mut() {
+                hook(id, component);
+            }
+        }
+        if self.queued.contains(&type_id) {
+            self.events.push(ComponentEvent{entity: id, type_id, kind});
+        }
+    }
+}
+
+fn hook<T, F>(mut callback: F) -> Hook
+    where T: 'static, F: FnMut(EntityId, &T) + 'static
+{
+    Box::new(move |id, component| {
+        callback(id, component.downcast_ref::<T>().expect("downcast to T"))
+    })
+}
+
+impl ComponentManager {
+    /// Call `callback` with every component of type T just after it is
+    /// inserted.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let physics = physics.clone();
+    /// cm.on_insert::<Collider, _>(move |entity, collider| {
+    ///     physics.borrow_mut().add(entity, collider.shape);
+    /// });
+    /// ```
+    pub fn on_insert<T, F>(&mut self, callback: F)
+        where T: 'static, F: FnMut(EntityId, &T) + 'static
+    {
+        self.hooks_mut().on_insert.entry(TypeId::of::<T>()).or_default().push(hook(callback));
+    }
+
+    /// Call `callback` with every component of type T just after it is
+    /// removed, and before it is dropped. Components removed by `remove`,
+    /// `remove_for`, `remove_one` and `purge` are all reported.
+    pub fn on_remove<T, F>(&mut self, callback: F)
+        where T: 'static, F: FnMut(EntityId, &T) + 'static
+    {
+        self.hooks_mut().on_remove.entry(TypeId::of::<T>()).or_default().push(hook(callback));
+    }
+
+    /// Start queueing a `ComponentEvent` for every component of type T that
+    /// is inserted or removed, to be taken with `drain_events`.
+    ///
+    /// Events accumulate until drained, so only queue types something will
+    /// drain.
+    pub fn queue_events<T>(&mut self)
+        where T: 'static
+    {
+        self.hooks_mut().queued.insert(TypeId::of::<T>());
+    }
+
+    /// Take the queued events, oldest first.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// for event in cm.drain_events() {
+    ///     if event.type_id == TypeId::of::<Collider>() && event.kind == ComponentEventKind::Removed {
+    ///         physics.remove(event.entity);
+    ///     }
+    /// }
+    /// ```
+    pub fn drain_events(&mut self) -> vec::Drain<'_, ComponentEvent>
+    {
+        self.hooks_mut().events.drain(..)
+    }
+}
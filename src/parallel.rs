@@ -0,0 +1,339 @@
+use std::any::{type_name, Any, TypeId};
+use std::collections::hash_map::HashMap;
+
+use change::Mut;
+use commands::Commands;
+use component::{Component, ComponentManager, EntityMeta};
+use entity::{EntityId, EntityManager};
+use error::Result;
+use resources::Resources;
+use storage::{AnyColumn, Column, Iter, IterFor};
+
+/// The component types a `ParallelProcessor` reads and writes. They must be
+/// `Send` and `Sync`, as the processor may run on another thread.
+///
+/// # Example
+///
+/// ```rust
+/// let access = Access::new().read::<Velocity>().write::<Position>();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Access {
+    reads: Vec<TypeId>,
+    writes: Vec<TypeId>,
+}
+
+impl Access {
+    pub fn new() -> Access
+    {
+        Access::default()
+    }
+
+    /// Declare that T is read.
+    ///
+    /// # Panics
+    ///
+    /// Panics if T is already declared, as read or as written; write access
+    /// covers reading.
+    pub fn read<T>(mut self) -> Access
+        where T: Component + Send + Sync
+    {
+        assert!(!self.writes.contains(&TypeId::of::<T>()), "{} is already declared as written", type_name::<T>());
+        assert!(!self.reads.contains(&TypeId::of::<T>()), "{} is already declared as read", type_name::<T>());
+        self.reads.push(TypeId::of::<T>());
+        self
+    }
+
+    /// Declare that T is written, and possibly read.
+    ///
+    /// # Panics
+    ///
+    /// Panics if T is already declared, as read or as written; write access
+    /// covers reading.
+    pub fn write<T>(mut self) -> Access
+        where T: Component + Send + Sync
+    {
+        assert!(!self.reads.contains(&TypeId::of::<T>()), "{} is already declared as read", type_name::<T>());
+        assert!(!self.writes.contains(&TypeId::of::<T>()), "{} is already declared as written", type_name::<T>());
+        self.writes.push(TypeId::of::<T>());
+        self
+    }
+
+    pub fn reads(&self) -> &[TypeId]
+    {
+        &self.reads
+    }
+
+    pub fn writes(&self) -> &[TypeId]
+    {
+        &self.writes
+    }
+
+    /// Whether running alongside a processor with the other access could
+    /// race, that is when either writes a type the other uses
+    pub fn conflicts(&self, other: &Access) -> bool
+    {
+        self.writes.iter().any(|type_id| other.reads.contains(type_id) || other.writes.contains(type_id))
+            || other.writes.iter().any(|type_id| self.reads.contains(type_id))
+    }
+}
+
+/// A processor that may run at the same time as others, see
+/// `World::add_parallel_processor`.
+///
+/// Unlike a `Processor` it cannot create entities or insert components; it
+/// only reads and writes the component types its `Access` declares.
+///
+/// # Example
+///
+/// ```rust
+/// struct Movement;
+///
+/// impl ParallelProcessor for Movement {
+///     fn access(&self) -> Access
+///     {
+///         Access::new().read::<Velocity>().write::<Position>()
+///     }
+///
+///     fn process(&mut self, view: &mut View)
+///     {
+///         for mut meta in view.writes.iter_mut::<Position>() {
+///             if let Some(velocity) = view.reads.get::<Velocity>(meta.entity) {
+///                 meta.component.x += velocity.x;
+///             }
+///         }
+///     }
+/// }
+/// ```
+pub trait ParallelProcessor: Send {
+    /// The types the processor uses. Asked once, when the processor is added.
+    fn access(&self) -> Access;
+
+    fn process(&mut self, view: &mut View);
+
+    /// The processor's own `Commands` buffer, through which it can make the
+    /// structural changes a `View` does not allow. The `World` applies it
+    /// once the processor's stage has run.
+    fn commands(&mut self) -> Option<&mut Commands>
+    {
+        None
+    }
+}
+
+/// What a `ParallelProcessor` sees of the world while it runs. The two halves
+/// are separate fields so reads can be made while iterating over writes.
+pub struct View<'a> {
+    pub reads: Reads<'a>,
+    pub writes: Writes<'a>,
+}
+
+/// Shared access to the entities, resources and the component types a
+/// parallel processor declared it reads
+#[derive(Clone, Copy)]
+pub struct Reads<'a> {
+    em: &'a EntityManager,
+    cm: &'a ComponentManager,
+    resources: &'a Resources,
+    access: &'a Access,
+}
+
+impl<'a> Reads<'a> {
+    fn check<T>(&self)
+        where T: Component
+    {
+        assert!(self.access.reads.contains(&TypeId::of::<T>()),
+                "processor did not declare that it reads {}", type_name::<T>());
+    }
+
+    pub fn entities(&self) -> &'a EntityManager
+    {
+        self.em
+    }
+
+    /// Iterate over every component of type T.
+    ///
+    /// # Panics
+    ///
+    /// Panics if T is not among the processor's declared reads, as with the
+    /// other component accessors.
+    pub fn iter<T>(&self) -> Iter<'a, T>
+        where T: Component + Send + Sync
+    {
+        self.check::<T>();
+        self.cm.iter()
+    }
+
+    pub fn iter_for<T>(&self, id: EntityId) -> IterFor<'a, T>
+        where T: Component + Send + Sync
+    {
+        self.check::<T>();
+        self.cm.iter_for(id)
+    }
+
+    /// The entity's first component of type T
+    pub fn get<T>(&self, id: EntityId) -> Option<&'a T>
+        where T: Component + Send + Sync
+    {
+        self.iter_for(id).next()
+    }
+
+    pub fn resource<R>(&self) -> &'a R
+        where R: Any + Sync
+    {
+        self.resources.resource()
+    }
+
+    pub fn try_resource<R>(&self) -> Result<&'a R>
+        where R: Any + Sync
+    {
+        self.resources.try_resource()
+    }
+}
+
+type Columns = HashMap<TypeId, Vec<(usize, Box<dyn AnyColumn>)>>;
+
+/// Exclusive access to the component types a parallel processor declared
+/// it writes. Components are marked changed as with
+/// `ComponentManager::iter_mut` and `get_mut`.
+pub struct Writes<'a> {
+    cm: &'a ComponentManager,
+    columns: &'a mut Columns,
+    tick: u64,
+}
+
+impl<'a> Writes<'a> {
+    fn columns<T>(&mut self) -> impl Iterator<Item = (usize, &mut Column<T>)>
+        where T: Component
+    {
+        self.columns.get_mut(&TypeId::of::<T>())
+            .unwrap_or_else(|| panic!("processor did not declare that it writes {}", type_name::<T>()))
+            .iter_mut()
+            .map(|&mut (table, ref mut column)| {
+                (table, column.as_any_mut().downcast_mut::<Column<T>>().expect("downcast to Column<T>"))
+            })
+    }
+
+    /// Iterate mutably over every component of type T
+    pub fn iter_mut<T>(&mut self) -> impl Iterator<Item = Mut<'_, EntityMeta<T>>>
+        where T: Component + Send + Sync
+    {
+        let tick = self.tick;
+        self.columns::<T>().flat_map(move |(_, column)| column.iter_mut(tick))
+    }
+
+    /// Mutable access to the entity's first component of type T
+    pub fn get_mut<T>(&mut self, id: EntityId) -> Option<Mut<'_, T>>
+        where T: Component + Send + Sync
+    {
+        let (tick, cm) = (self.tick, self.cm);
+        self.columns::<T>()
+            .find_map(|(table, column)| cm.tables()[table].row(id).map(|row| (column, row)))
+            .map(|(column, row)| column.get_mut(row, tick))
+    }
+}
+
+/// A due parallel processor along with the columns it writes
+struct Job<'p> {
+    processor: &'p mut Box<dyn ParallelProcessor>,
+    access: &'p Access,
+    columns: &'p mut Columns,
+}
+
+// Safety: the columns hold component types the job's `Access` declares it
+// writes, which `Access::write` requires to be `Send`.
+unsafe impl<'p> Send for Job<'p> {}
+
+/// What every job of a stage shares
+#[derive(Clone, Copy)]
+struct Shared<'a> {
+    em: &'a EntityManager,
+    cm: &'a ComponentManager,
+    resources: &'a Resources,
+    tick: u64,
+}
+
+// Safety: through `Reads` and `Writes` jobs only reach component types their
+// `Access` declares, which `Access` requires to be `Sync`, and resources
+// that are `Sync`. Nothing else in the manager or the resources is touched
+// while a stage runs.
+unsafe impl<'a> Sync for Shared<'a> {}
+
+/// The columns taken out of a manager for parallel processors, put back
+/// when dropped so a panicking processor does not leave them missing
+struct Taken<'a> {
+    cm: &'a mut ComponentManager,
+    columns: Vec<Columns>,
+}
+
+impl<'a> Drop for Taken<'a> {
+    fn drop(&mut self)
+    {
+        for columns in self.columns.drain(..) {
+            for (type_id, columns) in columns {
+                self.cm.restore_columns(type_id, columns);
+            }
+        }
+    }
+}
+
+/// Run parallel processors at the same time on the thread pool. Their
+/// accesses must not conflict.
+pub(crate) fn run(processors: Vec<(&mut Box<dyn ParallelProcessor>, &Access)>, em: &EntityManager,
+                  cm: &mut ComponentManager, resources: &Resources)
+{
+    use rayon::prelude::*;
+
+    let mut taken = Taken{cm, columns: Vec::with_capacity(processors.len())};
+    for &(_, access) in processors.iter() {
+        // Columns are handed to the guard as soon as they are taken, so a
+        // panic partway through still puts back the ones taken before it
+        taken.columns.push(Columns::new());
+        for &type_id in access.writes.iter() {
+            let columns = taken.cm.take_columns(type_id);
+            taken.columns.last_mut().expect("columns just pushed").insert(type_id, columns);
+        }
+    }
+
+    let shared = Shared{em, cm: &*taken.cm, resources, tick: taken.cm.change_tick()};
+    let mut jobs: Vec<Job> = processors.into_iter()
+        .zip(taken.columns.iter_mut())
+        .map(|((processor, access), columns)| Job{processor, access, columns})
+        .collect();
+    let job = |job: &mut Job| {
+        let Shared{em, cm, resources, tick} = shared;
+        let mut view = View {
+            reads: Reads{em, cm, resources, access: job.access},
+            writes: Writes{cm, columns: job.columns, tick},
+        };
+        job.processor.process(&mut view);
+    };
+    if jobs.len() == 1 {
+        job(&mut jobs[0]);
+    } else {
+        jobs.par_iter_mut().for_each(job);
+    }
+}
+
+/// Group processors into stages that can each run at once. A processor goes
+/// in the stage after the last one holding a processor it conflicts with,
+/// so conflicting processors keep their declared order. `conflicts(a, b)`
+/// is asked for every pair of positions with a before b.
+pub(crate) fn stages<F>(count: usize, conflicts: F) -> Vec<Vec<usize>>
+    where F: Fn(usize, usize) -> bool
+{
+    let mut levels: Vec<usize> = Vec::with_capacity(count);
+    let mut stages: Vec<Vec<usize>> = Vec::new();
+    for b in 0..count {
+        let level = (0..b)
+            .filter(|&a| conflicts(a, b))
+            .map(|a| levels[a] + 1)
+            .max()
+            .unwrap_or(0);
+        levels.push(level);
+        if level == stages.len() {
+            stages.push(Vec::new());
+        }
+        stages[level].push(b);
+    }
+    stages
+}
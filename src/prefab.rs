@@ -0,0 +1,205 @@
+use std::any::{Any, TypeId};
+use std::collections::hash_map::HashMap;
+
+use component::{Component, ComponentManager};
+use entity::{EntityId, EntityManager};
+use error::{EsError, Result};
+
+/// A component held by a prefab, cloned onto every entity spawned from it
+pub(crate) trait PrefabComponent {
+    fn component_type(&self) -> TypeId;
+    fn as_any(&self) -> &dyn Any;
+    fn clone_box(&self) -> Box<dyn PrefabComponent>;
+    fn insert_clone(&self, cm: &mut ComponentManager, id: EntityId);
+}
+
+struct Held<T>(T);
+
+impl<T> PrefabComponent for Held<T>
+    where T: Clone + Component
+{
+    fn component_type(&self) -> TypeId
+    {
+        TypeId::of::<T>()
+    }
+
+    fn as_any(&self) -> &dyn Any
+    {
+        &self.0
+    }
+
+    fn clone_box(&self) -> Box<dyn PrefabComponent>
+    {
+        held(self.0.clone())
+    }
+
+    fn insert_clone(&self, cm: &mut ComponentManager, id: EntityId)
+    {
+        cm.insert(id, self.0.clone());
+    }
+}
+
+pub(crate) fn held<T>(component: T) -> Box<dyn PrefabComponent>
+    where T: Clone + Component
+{
+    Box::new(Held(component))
+}
+
+/// A template of components to spawn entities from, holding at most one
+/// component of each type.
+///
+/// Override components for a single spawn by cloning the prefab and
+/// replacing them with `with`.
+///
+/// # Example
+///
+/// ```rust
+/// let goblin = Prefab::new()
+///     .with(Health{hp: 5})
+///     .with(Sprite{image: "goblin.png"});
+///
+/// let grunt = goblin.spawn(&mut em, &mut cm);
+/// let boss = goblin.clone()
+///     .with(Health{hp: 50})
+///     .spawn_named(&mut em, &mut cm, "boss")?;
+/// ```
+#[derive(Default)]
+pub struct Prefab {
+    components: Vec<Box<dyn PrefabComponent>>,
+}
+
+impl Clone for Prefab {
+    fn clone(&self) -> Prefab
+    {
+        Prefab{components: self.components.iter().map(|component| component.clone_box()).collect()}
+    }
+}
+
+impl Prefab {
+    pub fn new() -> Prefab
+    {
+        Prefab::default()
+    }
+
+    /// Add a component, replacing any the prefab has of the same type
+    pub fn with<T>(mut self, component: T) -> Prefab
+        where T: Clone + Component
+    {
+        self.set(component);
+        self
+    }
+
+    /// Add a component, replacing any the prefab has of the same type
+    pub fn set<T>(&mut self, component: T)
+        where T: Clone + Component
+    {
+        self.set_boxed(held(component));
+    }
+
+    pub(crate) fn set_boxed(&mut self, component: Box<dyn PrefabComponent>)
+    {
+        let type_id = component.component_type();
+        match self.components.iter().position(|c| c.component_type() == type_id) {
+            Some(i) => self.components[i] = component,
+            None => self.components.push(component),
+        }
+    }
+
+    pub fn get<T>(&self) -> Option<&T>
+        where T: Component
+    {
+        self.components.iter()
+            .find(|component| component.component_type() == TypeId::of::<T>())
+            .map(|component| component.as_any().downcast_ref::<T>().expect("downcast to T"))
+    }
+
+    /// Drop the component of type T, returning whether there was one
+    pub fn remove<T>(&mut self) -> bool
+        where T: Component
+    {
+        let len = self.components.len();
+        self.components.retain(|component| component.component_type() != TypeId::of::<T>());
+        self.components.len() != len
+    }
+
+    /// The number of components in the prefab
+    pub fn len(&self) -> usize
+    {
+        self.components.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.components.is_empty()
+    }
+
+    /// Insert a clone of each of the prefab's components into an existing
+    /// entity
+    pub fn insert_into(&self, cm: &mut ComponentManager, id: EntityId)
+    {
+        for component in self.components.iter() {
+            component.insert_clone(cm, id);
+        }
+    }
+
+    /// Create an entity holding clones of the prefab's components
+    pub fn spawn(&self, em: &mut EntityManager, cm: &mut ComponentManager) -> EntityId
+    {
+        let id = em.create();
+        self.insert_into(cm, id);
+        id
+    }
+
+    /// Create a named entity holding clones of the prefab's components, see
+    /// `EntityManager::try_create_named`
+    pub fn spawn_named<N>(&self, em: &mut EntityManager, cm: &mut ComponentManager, name: N) -> Result<EntityId>
+        where N: Into<String>
+    {
+        let id = em.try_create_named(name)?;
+        self.insert_into(cm, id);
+        Ok(id)
+    }
+}
+
+/// A library of prefabs by name, typically loaded with
+/// `Registry::load_prefabs`
+#[derive(Clone, Default)]
+pub struct Prefabs {
+    prefabs: HashMap<String, Prefab>,
+}
+
+impl Prefabs {
+    pub fn new() -> Prefabs
+    {
+        Prefabs::default()
+    }
+
+    /// Add a prefab, returning any it replaces
+    pub fn insert<N>(&mut self, name: N, prefab: Prefab) -> Option<Prefab>
+        where N: Into<String>
+    {
+        self.prefabs.insert(name.into(), prefab)
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Prefab>
+    {
+        self.prefabs.get(name).ok_or_else(|| EsError::NoSuchPrefab(name.to_string()))
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Prefab>
+    {
+        self.prefabs.remove(name)
+    }
+
+    /// Iterate over every prefab along with its name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Prefab)>
+    {
+        self.prefabs.iter().map(|(name, prefab)| (&**name, prefab))
+    }
+
+    /// Spawn an entity from the named prefab, see `Prefab::spawn`
+    pub fn spawn(&self, name: &str, em: &mut EntityManager, cm: &mut ComponentManager) -> Result<EntityId>
+    {
+        Ok(self.get(name)?.spawn(em, cm))
+    }
+}
@@ -0,0 +1,40 @@
+use commands::Commands;
+use entity::EntityManager;
+use component::ComponentManager;
+use events::EventBus;
+use resources::Resources;
+
+/// A Processor performs one global aspect of the game logic, such as physics
+/// or rendering, by iterating over and modifying components.
+///
+/// Processors are registered with a `World`, which runs them on their
+/// configured tick interval and hands them its resources and event bus
+/// alongside the managers.
+///
+/// # Example
+///
+/// ```rust
+/// struct Gravity;
+///
+/// impl Processor for Gravity {
+///     fn process(&mut self, _: &mut EntityManager, cm: &mut ComponentManager,
+///                resources: &mut Resources, _: &mut EventBus)
+///     {
+///         let scale = resources.resource::<GravityScale>().0;
+///         for mut meta in cm.find_mut::<Velocity>() {
+///             meta.component.y -= scale;
+///         }
+///     }
+/// }
+/// ```
+pub trait Processor {
+    fn process(&mut self, em: &mut EntityManager, cm: &mut ComponentManager,
+               resources: &mut Resources, events: &mut EventBus);
+
+    /// The processor's own `Commands` buffer, if it keeps one. The `World`
+    /// applies it after each tick's run of the processor.
+    fn commands(&mut self) -> Option<&mut Commands>
+    {
+        None
+    }
+}
@@ -0,0 +1,566 @@
+use std::any::TypeId;
+use std::collections::hash_map::HashMap;
+use std::marker::PhantomData;
+
+use entity::EntityId;
+use component::ComponentManager;
+use change::Mut;
+use storage::{Column, Table};
+
+/// One component type requested by a query: `&T`, `&mut T`, `Option<&T>` or
+/// `Option<&mut T>`.
+///
+/// When an entity has several components of the same type the query sees
+/// the first one, just like `ComponentManager::get`. Mutable requests yield
+/// a `Mut<T>` guard, marking the component changed only once written.
+pub trait Fetch<'a> {
+    type Item;
+    #[doc(hidden)]
+    type Column: Copy;
+
+    /// The component type accessed and whether it is accessed mutably
+    #[doc(hidden)]
+    fn access() -> (TypeId, bool);
+
+    /// Whether entities lacking the component are skipped
+    #[doc(hidden)]
+    const REQUIRED: bool;
+
+    /// Resolve the column within a table, for components changed at `tick`.
+    /// None means no entity in the table can match.
+    #[doc(hidden)]
+    unsafe fn column(table: Option<*mut Table>, tick: u64) -> Option<Self::Column>;
+
+    /// Fetch the component at `row`, or None if the entity does not match.
+    ///
+    /// Callers must not fetch the same row twice while an earlier item for
+    /// it is still alive.
+    #[doc(hidden)]
+    unsafe fn fetch(column: Self::Column, row: Option<usize>) -> Option<Self::Item>;
+}
+
+unsafe fn column_ptr<T>(table: Option<*mut Table>) -> Option<*mut Column<T>>
+    where T: 'static
+{
+    table.and_then(|table| (*table).column_mut::<T>())
+        .map(|column| column as *mut Column<T>)
+}
+
+impl<'a, T> Fetch<'a> for &'a T
+    where T: 'static
+{
+    type Item = &'a T;
+    type Column = *mut Column<T>;
+    const REQUIRED: bool = true;
+
+    fn access() -> (TypeId, bool)
+    {
+        (TypeId::of::<T>(), false)
+    }
+
+    unsafe fn column(table: Option<*mut Table>, _: u64) -> Option<Self::Column>
+    {
+        column_ptr(table)
+    }
+
+    unsafe fn fetch(column: Self::Column, row: Option<usize>) -> Option<Self::Item>
+    {
+        row.map(|row| &*Column::first_ptr(column, row))
+    }
+}
+
+impl<'a, T> Fetch<'a> for &'a mut T
+    where T: 'static
+{
+    type Item = Mut<'a, T>;
+    type Column = (*mut Column<T>, u64);
+    const REQUIRED: bool = true;
+
+    fn access() -> (TypeId, bool)
+    {
+        (TypeId::of::<T>(), true)
+    }
+
+    unsafe fn column(table: Option<*mut Table>, tick: u64) -> Option<Self::Column>
+    {
+        column_ptr(table).map(|column| (column, tick))
+    }
+
+    unsafe fn fetch((column, tick): Self::Column, row: Option<usize>) -> Option<Self::Item>
+    {
+        row.map(|row| Mut::new(&mut *Column::first_ptr(column, row),
+                               &mut *Column::first_ticks_ptr(column, row),
+                               tick))
+    }
+}
+
+impl<'a, T> Fetch<'a> for Option<&'a T>
+    where T: 'static
+{
+    type Item = Option<&'a T>;
+    type Column = Option<<&'a T as Fetch<'a>>::Column>;
+    const REQUIRED: bool = false;
+
+    fn access() -> (TypeId, bool)
+    {
+        (TypeId::of::<T>(), false)
+    }
+
+    unsafe fn column(table: Option<*mut Table>, tick: u64) -> Option<Self::Column>
+    {
+        Some(<&T as Fetch>::column(table, tick))
+    }
+
+    unsafe fn fetch(column: Self::Column, row: Option<usize>) -> Option<Self::Item>
+    {
+        Some(column.and_then(|column| <&T as Fetch>::fetch(column, row)))
+    }
+}
+
+impl<'a, T> Fetch<'a> for Option<&'a mut T>
+    where T: 'static
+{
+    type Item = Option<Mut<'a, T>>;
+    type Column = Option<<&'a mut T as Fetch<'a>>::Column>;
+    const REQUIRED: bool = false;
+
+    fn access() -> (TypeId, bool)
+    {
+        (TypeId::of::<T>(), true)
+    }
+
+    unsafe fn column(table: Option<*mut Table>, tick: u64) -> Option<Self::Column>
+    {
+        Some(<&mut T as Fetch>::column(table, tick))
+    }
+
+    unsafe fn fetch(column: Self::Column, row: Option<usize>) -> Option<Self::Item>
+    {
+        Some(column.and_then(|column| <&mut T as Fetch>::fetch(column, row)))
+    }
+}
+
+/// The table holding every component of a type that is not shared.
+///
+/// Every table pointer a query holds is derived from the one `tables` base
+/// it was started with, as reborrowing the table list for each would
+/// invalidate the pointers taken before.
+fn type_table(cm: &ComponentManager, tables: *mut Table, type_id: TypeId) -> Option<*mut Table>
+{
+    cm.type_table(type_id).map(|table| unsafe { tables.add(table) })
+}
+
+/// Where a fetch finds each entity's component
+#[doc(hidden)]
+#[derive(Clone, Copy)]
+pub enum Source<C> {
+    /// In the archetype table being walked, at the current row
+    Row(C),
+    /// In the type's own table, if it has one
+    Lookup(Option<*const Table>, C),
+    /// In whichever archetype table the entity lives in, for components
+    /// changed at the given tick
+    Located(*const HashMap<EntityId, usize>, *mut Table, u64),
+}
+
+/// Resolve a fetch, within `table` when walking archetype tables. None
+/// means no entity can match.
+fn source<'a, F>(cm: &ComponentManager, tables: *mut Table, table: Option<*mut Table>) -> Option<Source<F::Column>>
+    where F: Fetch<'a>
+{
+    let (type_id, tick) = (F::access().0, cm.change_tick());
+    if !cm.shares(type_id) {
+        let table = type_table(cm, tables, type_id);
+        return Some(Source::Lookup(table.map(|table| table as *const Table),
+                                   unsafe { F::column(table, tick) }?));
+    }
+    match table {
+        Some(table) => Some(Source::Row(unsafe { F::column(Some(table), tick) }?)),
+        None => Some(Source::Located(cm.locations() as *const _, tables, tick)),
+    }
+}
+
+unsafe fn fetch<'a, F>(source: Source<F::Column>, id: EntityId, row: usize) -> Option<F::Item>
+    where F: Fetch<'a>
+{
+    match source {
+        Source::Row(column) => F::fetch(column, Some(row)),
+        Source::Lookup(table, column) => F::fetch(column, table.and_then(|table| (*table).row(id))),
+        Source::Located(locations, tables, tick) => {
+            let table = (*locations).get(&id).map(|&table| tables.add(table));
+            F::fetch(F::column(table, tick)?, table.and_then(|table| (*table).row(id)))
+        },
+    }
+}
+
+/// Whether entities have a component of some type, as seen by a filter
+#[doc(hidden)]
+#[derive(Clone, Copy)]
+pub enum Presence {
+    /// The same for every entity of the archetype table being walked
+    Known(bool),
+    Lookup(Option<*const Table>),
+    Located(*const HashMap<EntityId, usize>, *const Table),
+}
+
+impl Presence {
+    fn new(cm: &ComponentManager, tables: *mut Table, type_id: TypeId, table: Option<*mut Table>) -> Presence
+    {
+        if !cm.shares(type_id) {
+            return Presence::Lookup(type_table(cm, tables, type_id).map(|table| table as *const Table));
+        }
+        match table {
+            Some(table) => Presence::Known(unsafe { &*table }.has(type_id)),
+            None => Presence::Located(cm.locations() as *const _, tables),
+        }
+    }
+
+    fn has(&self, type_id: TypeId, id: EntityId) -> bool
+    {
+        unsafe {
+            match *self {
+                Presence::Known(has) => has,
+                Presence::Lookup(table) => table.is_some_and(|table| (*table).row(id).is_some()),
+                Presence::Located(locations, tables) => (*locations).get(&id)
+                    .is_some_and(|&table| (*tables.add(table)).has(type_id)),
+            }
+        }
+    }
+}
+
+/// A restriction on which entities a query visits, without fetching any
+/// components: `With<T>`, `Without<T>`, or a tuple of filters.
+pub trait Filter {
+    #[doc(hidden)]
+    type State;
+
+    /// Resolve the filter, within `table` when walking archetype tables.
+    /// `tables` points to the first of the manager's tables. None means no
+    /// entity can match.
+    #[doc(hidden)]
+    fn init(cm: &ComponentManager, tables: *mut Table, table: Option<*mut Table>) -> Option<Self::State>;
+
+    #[doc(hidden)]
+    fn matches(state: &Self::State, id: EntityId) -> bool;
+}
+
+/// Only visit entities that have a component of type T
+pub struct With<T>(PhantomData<T>);
+
+/// Only visit entities that have no component of type T
+pub struct Without<T>(PhantomData<T>);
+
+impl<T> Filter for With<T>
+    where T: 'static
+{
+    type State = Presence;
+
+    fn init(cm: &ComponentManager, tables: *mut Table, table: Option<*mut Table>) -> Option<Self::State>
+    {
+        match Presence::new(cm, tables, TypeId::of::<T>(), table) {
+            Presence::Known(false) => None,
+            presence => Some(presence),
+        }
+    }
+
+    fn matches(state: &Self::State, id: EntityId) -> bool
+    {
+        state.has(TypeId::of::<T>(), id)
+    }
+}
+
+impl<T> Filter for Without<T>
+    where T: 'static
+{
+    type State = Presence;
+
+    fn init(cm: &ComponentManager, tables: *mut Table, table: Option<*mut Table>) -> Option<Self::State>
+    {
+        match Presence::new(cm, tables, TypeId::of::<T>(), table) {
+            Presence::Known(true) => None,
+            presence => Some(presence),
+        }
+    }
+
+    fn matches(state: &Self::State, id: EntityId) -> bool
+    {
+        !state.has(TypeId::of::<T>(), id)
+    }
+}
+
+impl Filter for () {
+    type State = ();
+
+    fn init(_: &ComponentManager, _: *mut Table, _: Option<*mut Table>) -> Option<()>
+    {
+        Some(())
+    }
+
+    fn matches(_: &(), _: EntityId) -> bool
+    {
+        true
+    }
+}
+
+/// A tuple of fetches joined over the entities that satisfy all of them.
+///
+/// The items yielded are the entity id followed by each fetched component.
+pub trait Query<'a> {
+    type Item;
+    #[doc(hidden)]
+    type State;
+
+    #[doc(hidden)]
+    fn access() -> Vec<(TypeId, bool)>;
+
+    /// Evaluating this fails to compile unless at least one fetch is
+    /// required, as a query of nothing but `Option`s has no entities to
+    /// start from
+    #[doc(hidden)]
+    const REQUIRES_COMPONENT: ();
+
+    /// The component types an entity must have to match
+    #[doc(hidden)]
+    fn required() -> Vec<TypeId>;
+
+    /// Resolve every fetch, within `table` when walking archetype tables.
+    /// `tables` points to the first of the manager's tables. None means no
+    /// entity can match.
+    #[doc(hidden)]
+    fn init(cm: &ComponentManager, tables: *mut Table, table: Option<*mut Table>) -> Option<Self::State>;
+
+    /// The entities of the smallest required type with a table of its own
+    #[doc(hidden)]
+    fn entities(state: &Self::State) -> Vec<EntityId>;
+
+    /// Fetch the entity's components, or None if the entity does not match.
+    /// `row` is the entity's row in the archetype table being walked.
+    #[doc(hidden)]
+    unsafe fn fetch(state: &Self::State, id: EntityId, row: usize) -> Option<Self::Item>;
+}
+
+/// Panics if the same component type is requested mutably more than once, or
+/// both mutably and immutably, as that would hand out aliasing references.
+fn check_access(access: &[(TypeId, bool)])
+{
+    for (i, &(type_id, mutable)) in access.iter().enumerate() {
+        for &(other, other_mutable) in access[i + 1..].iter() {
+            assert!(type_id != other || !(mutable || other_mutable),
+                    "query requests the same component type mutably more than once");
+        }
+    }
+}
+
+macro_rules! impl_query {
+    ($(($fetch:ident, $state:ident, $item:ident)),+) => {
+        impl<'a, $($fetch),+> Query<'a> for ($($fetch,)+)
+            where $($fetch: Fetch<'a>),+
+        {
+            type Item = (EntityId, $($fetch::Item),+);
+            type State = ($(Source<$fetch::Column>,)+);
+
+            const REQUIRES_COMPONENT: () = assert!(false $(|| $fetch::REQUIRED)+,
+                                                   "a query must request at least one component that is not an Option");
+
+            fn access() -> Vec<(TypeId, bool)>
+            {
+                vec![$($fetch::access()),+]
+            }
+
+            fn required() -> Vec<TypeId>
+            {
+                let mut required = Vec::new();
+                $(
+                    if $fetch::REQUIRED {
+                        required.push($fetch::access().0);
+                    }
+                )+
+                required
+            }
+
+            fn init(cm: &ComponentManager, tables: *mut Table, table: Option<*mut Table>) -> Option<Self::State>
+            {
+                Some(($(source::<$fetch>(cm, tables, table)?,)+))
+            }
+
+            fn entities(state: &Self::State) -> Vec<EntityId>
+            {
+                let ($($state,)+) = *state;
+                let mut smallest: Option<&[EntityId]> = None;
+                $(
+                    if let (true, Source::Lookup(Some(table), _)) = ($fetch::REQUIRED, $state) {
+                        let entities = unsafe { &*table }.entities();
+                        if smallest.is_none_or(|min| entities.len() < min.len()) {
+                            smallest = Some(entities);
+                        }
+                    }
+                )+
+                smallest.map(|entities| entities.to_vec()).unwrap_or_default()
+            }
+
+            unsafe fn fetch(state: &Self::State, id: EntityId, row: usize) -> Option<Self::Item>
+            {
+                let ($($state,)+) = *state;
+                $(let $item = fetch::<$fetch>($state, id, row)?;)+
+                Some((id, $($item),+))
+            }
+        }
+    }
+}
+
+impl_query!((A, a, item_a));
+impl_query!((A, a, item_a), (B, b, item_b));
+impl_query!((A, a, item_a), (B, b, item_b), (C, c, item_c));
+impl_query!((A, a, item_a), (B, b, item_b), (C, c, item_c), (D, d, item_d));
+impl_query!((A, a, item_a), (B, b, item_b), (C, c, item_c), (D, d, item_d),
+            (E, e, item_e));
+impl_query!((A, a, item_a), (B, b, item_b), (C, c, item_c), (D, d, item_d),
+            (E, e, item_e), (F, f, item_f));
+
+macro_rules! impl_filter {
+    ($(($filter:ident, $state:ident)),+) => {
+        impl<$($filter),+> Filter for ($($filter,)+)
+            where $($filter: Filter),+
+        {
+            type State = ($($filter::State,)+);
+
+            fn init(cm: &ComponentManager, tables: *mut Table, table: Option<*mut Table>) -> Option<Self::State>
+            {
+                Some(($($filter::init(cm, tables, table)?,)+))
+            }
+
+            fn matches(state: &Self::State, id: EntityId) -> bool
+            {
+                let ($(ref $state,)+) = *state;
+                $($filter::matches($state, id))&&+
+            }
+        }
+    }
+}
+
+impl_filter!((A, a));
+impl_filter!((A, a), (B, b));
+impl_filter!((A, a), (B, b), (C, c));
+impl_filter!((A, a), (B, b), (C, c), (D, d));
+
+enum Walk<'a, Q, F>
+    where Q: Query<'a>, F: Filter
+{
+    /// Look each entity of the smallest required type up in the other types'
+    /// tables
+    Entities {
+        state: Q::State,
+        filter: F::State,
+        entities: ::std::vec::IntoIter<EntityId>,
+    },
+    /// Walk the rows of every archetype table holding the required shared
+    /// types
+    Tables {
+        cm: *const ComponentManager,
+        tables: *mut Table,
+        next: usize,
+        current: Option<(*const Table, Q::State, F::State)>,
+        row: usize,
+    },
+    Done,
+}
+
+/// Iterator over the results of `ComponentManager::query`
+pub struct QueryIter<'a, Q, F>
+    where Q: Query<'a>, F: Filter
+{
+    walk: Walk<'a, Q, F>,
+    _borrow: PhantomData<&'a mut ComponentManager>,
+}
+
+impl<'a, Q, F> QueryIter<'a, Q, F>
+    where Q: Query<'a>, F: Filter
+{
+    pub(crate) fn new(cm: &'a mut ComponentManager) -> QueryIter<'a, Q, F>
+    {
+        let () = Q::REQUIRES_COMPONENT;
+        check_access(&Q::access());
+        let required = Q::required();
+        let tables = cm.tables_mut().as_mut_ptr();
+        let cm = &*cm;
+
+        let walk = if required.iter().any(|&type_id| cm.shares(type_id)) {
+            Walk::Tables {
+                cm: cm as *const ComponentManager,
+                tables,
+                next: 0,
+                current: None,
+                row: 0,
+            }
+        } else {
+            match (Q::init(cm, tables, None), F::init(cm, tables, None)) {
+                (Some(state), Some(filter)) => Walk::Entities {
+                    entities: Q::entities(&state).into_iter(),
+                    state,
+                    filter,
+                },
+                _ => Walk::Done,
+            }
+        };
+        QueryIter {
+            walk,
+            _borrow: PhantomData,
+        }
+    }
+}
+
+impl<'a, Q, F> Iterator for QueryIter<'a, Q, F>
+    where Q: Query<'a>, F: Filter
+{
+    type Item = Q::Item;
+
+    fn next(&mut self) -> Option<Q::Item>
+    {
+        // Safety: every entity is visited once and `check_access` ruled out
+        // aliasing between the fetched types.
+        match self.walk {
+            Walk::Entities{ref state, ref filter, ref mut entities} => {
+                for id in entities.by_ref() {
+                    if !F::matches(filter, id) {
+                        continue;
+                    }
+                    // No fetch reads the row outside of archetype tables
+                    if let Some(item) = unsafe { Q::fetch(state, id, 0) } {
+                        return Some(item);
+                    }
+                }
+                None
+            },
+            Walk::Tables{cm, tables, ref mut next, ref mut current, ref mut row} => {
+                loop {
+                    if let Some((table, ref state, ref filter)) = *current {
+                        let table = unsafe { &*table };
+                        while *row < table.len() {
+                            let (id, at) = (table.entities()[*row], *row);
+                            *row += 1;
+                            if !F::matches(filter, id) {
+                                continue;
+                            }
+                            if let Some(item) = unsafe { Q::fetch(state, id, at) } {
+                                return Some(item);
+                            }
+                        }
+                    }
+
+                    let cm = unsafe { &*cm };
+                    if *next == cm.tables().len() {
+                        return None;
+                    }
+                    let table = unsafe { tables.add(*next) };
+                    *next += 1;
+                    *row = 0;
+                    *current = match (Q::init(cm, tables, Some(table)), F::init(cm, tables, Some(table))) {
+                        (Some(state), Some(filter)) => Some((table as *const Table, state, filter)),
+                        _ => None,
+                    };
+                }
+            },
+            Walk::Done => None,
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use std::any::{type_name, Any, TypeId};
+use std::fmt::{self, Debug, Display};
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+
+use component::ComponentManager;
+use entity::EntityId;
+use error::{EsError, Result};
+use registry::{Entry, Registry};
+
+pub(crate) type FormatFn = fn(&dyn Any, &mut fmt::Formatter) -> fmt::Result;
+
+fn format<T>(component: &dyn Any, f: &mut fmt::Formatter) -> fmt::Result
+    where T: Debug + 'static
+{
+    Debug::fmt(component.downcast_ref::<T>().expect("downcast to T"), f)
+}
+
+fn to_value<T>(component: &dyn Any) -> Result<Value>
+    where T: Serialize + 'static
+{
+    serde_json::to_value(component.downcast_ref::<T>().expect("downcast to T"))
+        .map_err(|err| EsError::Serialization(err.to_string()))
+}
+
+fn from_value<T>(value: Value) -> serde_json::Result<Box<dyn Any>>
+    where T: DeserializeOwned + 'static
+{
+    serde_json::from_value::<T>(value).map(|component| Box::new(component) as Box<dyn Any>)
+}
+
+fn replace<T>(component: &mut dyn Any, value: Box<dyn Any>)
+    where T: 'static
+{
+    *component.downcast_mut::<T>().expect("downcast to T") = *value.downcast::<T>().expect("downcast to T");
+}
+
+/// Converts single components of one registered type to and from JSON
+/// values
+pub(crate) struct Fields {
+    to_value: fn(&dyn Any) -> Result<Value>,
+    from_value: fn(Value) -> serde_json::Result<Box<dyn Any>>,
+    replace: fn(&mut dyn Any, Box<dyn Any>),
+}
+
+impl Fields {
+    pub fn new<T>() -> Fields
+        where T: Serialize + DeserializeOwned + 'static
+    {
+        Fields {
+            to_value: to_value::<T>,
+            from_value: from_value::<T>,
+            replace: replace::<T>,
+        }
+    }
+}
+
+/// Formats a type erased component through its registered function
+struct Formatted<'a>(&'a dyn Any, FormatFn);
+
+impl<'a> Display for Formatted<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        (self.1)(self.0, f)
+    }
+}
+
+/// The fields of a component converted to a JSON value: the members of an
+/// object, or the positions of an array for tuple structs
+fn field_names(value: &Value) -> Vec<String>
+{
+    match *value {
+        Value::Object(ref members) => members.keys().cloned().collect(),
+        Value::Array(ref items) => (0..items.len()).map(|index| index.to_string()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn field_mut<'a>(value: &'a mut Value, field: &str) -> Option<&'a mut Value>
+{
+    match *value {
+        Value::Object(ref mut members) => members.get_mut(field),
+        Value::Array(ref mut items) => field.parse::<usize>().ok().and_then(move |index| items.get_mut(index)),
+        _ => None,
+    }
+}
+
+impl Registry {
+    /// Give a registered component type debug output for `dump`, which
+    /// otherwise shows its components as JSON.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the type is not registered.
+    pub fn register_debug<T>(&mut self)
+        where T: Debug + 'static
+    {
+        let entry = self.entry_of_type_mut(TypeId::of::<T>())
+            .unwrap_or_else(|| panic!("component type {} is not registered", type_name::<T>()));
+        entry.format = Some(format::<T>);
+    }
+
+    /// Describe every component of an entity, one per line as
+    /// `name: debug output`, sorted by name. Types that are not registered
+    /// are listed by their Rust name alone.
+    pub fn dump(&self, cm: &ComponentManager, id: EntityId) -> String
+    {
+        let mut lines = Vec::new();
+        for component_type in cm.component_types_of(id, self) {
+            let type_id = component_type.type_id;
+            let entry = match self.entry_of_type(type_id) {
+                Some(entry) => entry,
+                None => {
+                    lines.push(component_type.type_name.to_string());
+                    continue;
+                },
+            };
+            let (table, row) = cm.locate_type(type_id, id)
+                .expect("entity to hold its component types");
+            for component in cm.tables()[table].row_components(type_id, row) {
+                match entry.format {
+                    Some(format) => lines.push(format!("{}: {}", entry.name, Formatted(component, format))),
+                    None => match (entry.fields.to_value)(component) {
+                        Ok(value) => lines.push(format!("{}: {}", entry.name, value)),
+                        Err(err) => lines.push(format!("{}: <{}>", entry.name, err)),
+                    },
+                }
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// The names of the fields of the entity's first component of a
+    /// registered type, in alphabetical order. Tuple structs have fields
+    /// named by position. Fields are found by converting the component to
+    /// JSON.
+    pub fn field_names(&self, cm: &ComponentManager, id: EntityId, name: &str) -> Result<Vec<String>>
+    {
+        let entry = self.entry(name.to_string())?;
+        Ok(field_names(&first_value(entry, cm, id)?))
+    }
+
+    /// Read a field of the entity's first component of a registered type.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// // In an editor, knowing only what the user typed
+    /// let x = registry.field(&cm, selected, "Position", "x")?;
+    /// registry.set_field(&mut cm, selected, "Position", "x", json!(x.as_i64().unwrap() + 1))?;
+    /// ```
+    pub fn field(&self, cm: &ComponentManager, id: EntityId, name: &str, field: &str) -> Result<Value>
+    {
+        let entry = self.entry(name.to_string())?;
+        let mut value = first_value(entry, cm, id)?;
+        field_mut(&mut value, field)
+            .map(|value| value.take())
+            .ok_or_else(|| EsError::NoSuchField(name.to_string(), field.to_string()))
+    }
+
+    /// Write a field of the entity's first component of a registered type,
+    /// marking the component changed. The component is left alone if the
+    /// value does not fit the field.
+    pub fn set_field(&self, cm: &mut ComponentManager, id: EntityId, name: &str, field: &str, value: Value) -> Result<()>
+    {
+        let entry = self.entry(name.to_string())?;
+        let mut component = first_value(entry, cm, id)?;
+        match field_mut(&mut component, field) {
+            Some(old) => *old = value,
+            None => return Err(EsError::NoSuchField(name.to_string(), field.to_string())),
+        }
+
+        let component = (entry.fields.from_value)(component)
+            .map_err(|err| EsError::InvalidFieldValue(name.to_string(), field.to_string(), err.to_string()))?;
+        let (table, row) = cm.locate_type(entry.type_id, id).expect("entity to hold the component");
+        let tick = cm.change_tick();
+        let target = cm.tables_mut()[table].first_component_mut(entry.type_id, row, tick)
+            .expect("located table to hold the type");
+        (entry.fields.replace)(target, component);
+        Ok(())
+    }
+}
+
+/// The entity's first component of a registered type, as JSON
+fn first_value(entry: &Entry, cm: &ComponentManager, id: EntityId) -> Result<Value>
+{
+    let (table, row) = cm.locate_type(entry.type_id, id)
+        .ok_or_else(|| cm.missing_type(entry.type_id, entry.type_name, id))?;
+    let components = cm.tables()[table].row_components(entry.type_id, row);
+    (entry.fields.to_value)(components[0])
+}
@@ -0,0 +1,406 @@
+use std::any::{type_name, TypeId};
+use std::collections::BTreeMap;
+use std::collections::hash_map::HashMap;
+use std::fmt::Display;
+use std::io::{Read, Write};
+
+use bincode;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+
+use component::{Component, ComponentManager, EntityMeta};
+use entity::EntityManager;
+use error::{EsError, Result};
+use prefab::{self, Prefab, PrefabComponent, Prefabs};
+use reflect::{Fields, FormatFn};
+
+/// The encodings a world can be saved in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Human readable JSON
+    Json,
+    /// Compact binary, using bincode
+    Binary,
+}
+
+/// Bumped whenever the layout of a save changes incompatibly
+const VERSION: u32 = 1;
+
+/// The layout of a save. Components are keyed by their registered name and
+/// hold that type's `EntityMeta` list, encoded on its own.
+#[derive(Serialize, Deserialize)]
+struct Save<E, V> {
+    version: u32,
+    entities: E,
+    components: BTreeMap<String, V>,
+}
+
+fn serialization<E>(err: E) -> EsError
+    where E: Display
+{
+    EsError::Serialization(err.to_string())
+}
+
+/// One of the encodings behind `Format`
+trait Codec: Sized {
+    /// A single component type's list, as embedded in a save
+    type Value: Serialize + DeserializeOwned;
+
+    fn functions(entry: &Entry) -> &Functions<Self>;
+    fn encode<T: Serialize>(value: &T) -> Result<Self::Value>;
+    fn decode<T: DeserializeOwned>(value: Self::Value) -> Result<T>;
+    fn write<W: Write, T: Serialize>(writer: W, value: &T) -> Result<()>;
+    fn read<R: Read, T: DeserializeOwned>(reader: R) -> Result<T>;
+}
+
+struct Json;
+struct Binary;
+
+impl Codec for Json {
+    type Value = Value;
+
+    fn functions(entry: &Entry) -> &Functions<Json>
+    {
+        &entry.json
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Value>
+    {
+        serde_json::to_value(value).map_err(serialization)
+    }
+
+    fn decode<T: DeserializeOwned>(value: Value) -> Result<T>
+    {
+        serde_json::from_value(value).map_err(serialization)
+    }
+
+    fn write<W: Write, T: Serialize>(writer: W, value: &T) -> Result<()>
+    {
+        serde_json::to_writer(writer, value).map_err(serialization)
+    }
+
+    fn read<R: Read, T: DeserializeOwned>(reader: R) -> Result<T>
+    {
+        serde_json::from_reader(reader).map_err(serialization)
+    }
+}
+
+impl Codec for Binary {
+    type Value = Vec<u8>;
+
+    fn functions(entry: &Entry) -> &Functions<Binary>
+    {
+        &entry.binary
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>>
+    {
+        bincode::serialize(value).map_err(serialization)
+    }
+
+    fn decode<T: DeserializeOwned>(value: Vec<u8>) -> Result<T>
+    {
+        bincode::deserialize(&value).map_err(serialization)
+    }
+
+    fn write<W: Write, T: Serialize>(writer: W, value: &T) -> Result<()>
+    {
+        bincode::serialize_into(writer, value).map_err(serialization)
+    }
+
+    fn read<R: Read, T: DeserializeOwned>(reader: R) -> Result<T>
+    {
+        bincode::deserialize_from(reader).map_err(serialization)
+    }
+}
+
+type LoadPrefab<V> = fn(V) -> Result<Box<dyn PrefabComponent>>;
+
+/// Saves and loads one component type in one encoding
+struct Functions<C: Codec> {
+    /// None if no component of the type exists
+    save: fn(&ComponentManager) -> Result<Option<C::Value>>,
+    load: fn(&mut ComponentManager, C::Value) -> Result<()>,
+    /// Decodes a single component for a prefab, for cloneable types
+    prefab: Option<LoadPrefab<C::Value>>,
+}
+
+impl<C: Codec> Functions<C> {
+    fn new<T>() -> Functions<C>
+        where T: Serialize + DeserializeOwned + Component
+    {
+        Functions {
+            save: save_components::<C, T>,
+            load: load_components::<C, T>,
+            prefab: None,
+        }
+    }
+
+    fn cloneable<T>() -> Functions<C>
+        where T: Serialize + DeserializeOwned + Clone + Component
+    {
+        Functions{prefab: Some(load_prefab_component::<C, T>), ..Functions::new::<T>()}
+    }
+}
+
+fn save_components<C, T>(cm: &ComponentManager) -> Result<Option<C::Value>>
+    where C: Codec, T: Serialize + 'static
+{
+    if !cm.contains::<T>() {
+        return Ok(None);
+    }
+    let components: Vec<&EntityMeta<T>> = cm.iter::<T>().collect();
+    C::encode(&components).map(Some)
+}
+
+fn load_components<C, T>(cm: &mut ComponentManager, value: C::Value) -> Result<()>
+    where C: Codec, T: DeserializeOwned + Component
+{
+    let components: Vec<EntityMeta<T>> = C::decode(value)?;
+    for meta in components {
+        cm.insert(meta.entity, meta.component);
+    }
+    Ok(())
+}
+
+fn load_prefab_component<C, T>(value: C::Value) -> Result<Box<dyn PrefabComponent>>
+    where C: Codec, T: DeserializeOwned + Clone + Component
+{
+    let component: T = C::decode(value)?;
+    Ok(prefab::held(component))
+}
+
+pub(crate) struct Entry {
+    pub(crate) name: String,
+    pub(crate) type_id: TypeId,
+    pub(crate) type_name: &'static str,
+    json: Functions<Json>,
+    binary: Functions<Binary>,
+    pub(crate) fields: Fields,
+    /// None unless given with `register_debug`
+    pub(crate) format: Option<FormatFn>,
+}
+
+impl Entry {
+    fn new<T>(name: &str, json: Functions<Json>, binary: Functions<Binary>) -> Entry
+        where T: Serialize + DeserializeOwned + Component
+    {
+        Entry {
+            name: name.to_string(),
+            type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
+            json,
+            binary,
+            fields: Fields::new::<T>(),
+            format: None,
+        }
+    }
+}
+
+/// Component types that can be saved, each under a stable name.
+///
+/// Only registered types are written by `save`; components of any other
+/// type are left out. Entity ids, names and multiple components of the same
+/// type on one entity all survive a round trip.
+///
+/// # Example
+///
+/// ```rust
+/// let mut registry = Registry::new();
+/// registry.register::<Position>("Position");
+///
+/// let mut buffer = Vec::new();
+/// registry.save(&em, &cm, Format::Json, &mut buffer)?;
+/// let (em, cm) = registry.load(Format::Json, &buffer[..])?;
+/// ```
+///
+/// Types registered with `register_cloneable` can also make up prefabs
+/// read by `load_prefabs`. Registered components can also be described with
+/// `dump` and edited field by field, see `Registry::field`.
+pub struct Registry {
+    entries: Vec<Entry>,
+    names: HashMap<String, usize>,
+    types: HashMap<TypeId, usize>,
+}
+
+impl Default for Registry {
+    fn default() -> Registry
+    {
+        Registry::new()
+    }
+}
+
+impl Registry {
+    pub fn new() -> Registry
+    {
+        Registry {
+            entries: Vec::new(),
+            names: HashMap::new(),
+            types: HashMap::new(),
+        }
+    }
+
+    /// Register a component type under a name, which saves refer to it by.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name or the type is already registered.
+    pub fn register<T>(&mut self, name: &str)
+        where T: Serialize + DeserializeOwned + Component
+    {
+        self.add(Entry::new::<T>(name, Functions::new::<T>(), Functions::new::<T>()));
+    }
+
+    /// Register a component type as with `register`, allowing it in
+    /// prefabs too
+    pub fn register_cloneable<T>(&mut self, name: &str)
+        where T: Serialize + DeserializeOwned + Clone + Component
+    {
+        self.add(Entry::new::<T>(name, Functions::cloneable::<T>(), Functions::cloneable::<T>()));
+    }
+
+    fn add(&mut self, entry: Entry)
+    {
+        assert!(!self.names.contains_key(&entry.name), "component name {} is already registered", entry.name);
+        assert!(!self.types.contains_key(&entry.type_id), "component type {} is already registered", entry.type_name);
+
+        let index = self.entries.len();
+        self.names.insert(entry.name.clone(), index);
+        self.types.insert(entry.type_id, index);
+        self.entries.push(entry);
+    }
+
+    /// The name a component type was registered under
+    pub fn name_of<T>(&self) -> Option<&str>
+        where T: 'static
+    {
+        self.name_of_type(TypeId::of::<T>())
+    }
+
+    /// The name a component type was registered under, by its `TypeId`
+    pub fn name_of_type(&self, type_id: TypeId) -> Option<&str>
+    {
+        self.entry_of_type(type_id).map(|entry| &*entry.name)
+    }
+
+    /// The type registered under a name
+    pub fn type_named(&self, name: &str) -> Option<TypeId>
+    {
+        self.names.get(name).map(|&index| self.entries[index].type_id)
+    }
+
+    /// Write the entities and every registered component type
+    pub fn save<W>(&self, em: &EntityManager, cm: &ComponentManager, format: Format, writer: W) -> Result<()>
+        where W: Write
+    {
+        match format {
+            Format::Json => self.save_with::<Json, W>(em, cm, writer),
+            Format::Binary => self.save_with::<Binary, W>(em, cm, writer),
+        }
+    }
+
+    /// Read a world written by `save` into new managers
+    pub fn load<R>(&self, format: Format, reader: R) -> Result<(EntityManager, ComponentManager)>
+        where R: Read
+    {
+        let mut cm = ComponentManager::new();
+        let em = self.load_into(format, reader, &mut cm)?;
+        Ok((em, cm))
+    }
+
+    /// Read a world written by `save`, inserting its components into `cm`.
+    ///
+    /// Use this to load into a manager set up with archetypes or storage
+    /// kinds; `cm` would normally hold no components yet.
+    pub fn load_into<R>(&self, format: Format, reader: R, cm: &mut ComponentManager) -> Result<EntityManager>
+        where R: Read
+    {
+        match format {
+            Format::Json => self.load_with::<Json, R>(reader, cm),
+            Format::Binary => self.load_with::<Binary, R>(reader, cm),
+        }
+    }
+
+    /// Read a library of prefabs. The data maps each prefab's name to its
+    /// components, keyed by registered name.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// // {"goblin": {"Health": {"hp": 5}, "Sprite": {"image": "goblin.png"}}}
+    /// let prefabs = registry.load_prefabs(Format::Json, File::open("prefabs.json")?)?;
+    /// let goblin = prefabs.spawn("goblin", &mut em, &mut cm)?;
+    /// ```
+    pub fn load_prefabs<R>(&self, format: Format, reader: R) -> Result<Prefabs>
+        where R: Read
+    {
+        match format {
+            Format::Json => self.load_prefabs_with::<Json, R>(reader),
+            Format::Binary => self.load_prefabs_with::<Binary, R>(reader),
+        }
+    }
+
+    pub(crate) fn entry(&self, name: String) -> Result<&Entry>
+    {
+        self.names.get(&name)
+            .map(|&index| &self.entries[index])
+            .ok_or(EsError::UnregisteredComponentType(name))
+    }
+
+    pub(crate) fn entry_of_type(&self, type_id: TypeId) -> Option<&Entry>
+    {
+        self.types.get(&type_id).map(|&index| &self.entries[index])
+    }
+
+    pub(crate) fn entry_of_type_mut(&mut self, type_id: TypeId) -> Option<&mut Entry>
+    {
+        let entries = &mut self.entries;
+        self.types.get(&type_id).map(move |&index| &mut entries[index])
+    }
+
+    fn save_with<C, W>(&self, em: &EntityManager, cm: &ComponentManager, writer: W) -> Result<()>
+        where C: Codec, W: Write
+    {
+        let mut components = BTreeMap::new();
+        for entry in self.entries.iter() {
+            if let Some(value) = (C::functions(entry).save)(cm)? {
+                components.insert(entry.name.clone(), value);
+            }
+        }
+        C::write(writer, &Save{version: VERSION, entities: em, components})
+    }
+
+    fn load_with<C, R>(&self, reader: R, cm: &mut ComponentManager) -> Result<EntityManager>
+        where C: Codec, R: Read
+    {
+        let save: Save<EntityManager, C::Value> = C::read(reader)?;
+        if save.version != VERSION {
+            return Err(EsError::Serialization(format!("unsupported save version {}", save.version)));
+        }
+        for (name, value) in save.components {
+            let entry = self.entry(name)?;
+            (C::functions(entry).load)(cm, value)?;
+        }
+        Ok(save.entities)
+    }
+
+    fn load_prefabs_with<C, R>(&self, reader: R) -> Result<Prefabs>
+        where C: Codec, R: Read
+    {
+        let data: BTreeMap<String, BTreeMap<String, C::Value>> = C::read(reader)?;
+        let mut prefabs = Prefabs::new();
+        for (name, components) in data {
+            let mut prefab = Prefab::new();
+            for (type_name, value) in components {
+                let entry = self.entry(type_name)?;
+                let load = C::functions(entry).prefab.ok_or_else(|| {
+                    EsError::Serialization(format!("component type {} is not registered as cloneable", entry.name))
+                })?;
+                prefab.set_boxed(load(value)?);
+            }
+            prefabs.insert(name, prefab);
+        }
+        Ok(prefabs)
+    }
+}
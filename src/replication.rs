@@ -0,0 +1,356 @@
+use std::any::TypeId;
+use std::collections::hash_map::HashMap;
+use std::collections::BTreeSet;
+use std::io::{Read, Write};
+
+use bincode;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+
+use component::{Component, ComponentManager};
+use entity::{EntityId, EntityManager};
+use error::{EsError, Result};
+
+fn serialization(err: bincode::Error) -> EsError
+{
+    EsError::Serialization(err.to_string())
+}
+
+/// One change within a diff. Entities are named by their server id and
+/// component types by their index in `ReplicatedTypes`.
+#[derive(Serialize, Deserialize)]
+enum Op {
+    Created(EntityId),
+    Destroyed(EntityId),
+    /// Every component of the type on the entity, encoded as a list
+    Added(EntityId, u32, Vec<u8>),
+    Changed(EntityId, u32, Vec<u8>),
+    Removed(EntityId, u32),
+}
+
+/// Replicates one component type, on either end
+#[derive(Clone)]
+struct Functions {
+    type_id: TypeId,
+    /// Entities whose components of the type were added, changed or removed
+    /// after a change tick
+    touched: fn(&ComponentManager, u64) -> Vec<EntityId>,
+    /// None if the entity has no component of the type
+    encode: fn(&ComponentManager, EntityId) -> Result<Option<Vec<u8>>>,
+    insert: fn(&mut ComponentManager, EntityId, &[u8]) -> Result<()>,
+    change: fn(&mut ComponentManager, EntityId, &[u8]) -> Result<()>,
+    remove: fn(&mut ComponentManager, EntityId),
+}
+
+impl Functions {
+    fn new<T>() -> Functions
+        where T: Serialize + DeserializeOwned + Component
+    {
+        Functions {
+            type_id: TypeId::of::<T>(),
+            touched: touched::<T>,
+            encode: encode::<T>,
+            insert: insert::<T>,
+            change: change::<T>,
+            remove: remove::<T>,
+        }
+    }
+}
+
+fn touched<T>(cm: &ComponentManager, since: u64) -> Vec<EntityId>
+    where T: Component
+{
+    let mut ids = cm.changed_since::<T>(since);
+    ids.extend(cm.removed_since::<T>(since));
+    ids
+}
+
+fn encode<T>(cm: &ComponentManager, id: EntityId) -> Result<Option<Vec<u8>>>
+    where T: Serialize + Component
+{
+    let components: Vec<&T> = cm.iter_for::<T>(id).collect();
+    if components.is_empty() {
+        return Ok(None);
+    }
+    bincode::serialize(&components).map(Some).map_err(serialization)
+}
+
+fn insert<T>(cm: &mut ComponentManager, id: EntityId, bytes: &[u8]) -> Result<()>
+    where T: DeserializeOwned + Component
+{
+    let components: Vec<T> = bincode::deserialize(bytes).map_err(serialization)?;
+    for component in components {
+        cm.insert(id, component);
+    }
+    Ok(())
+}
+
+/// Overwrite the entity's components in place when it holds as many as were
+/// sent, and replace them otherwise
+fn change<T>(cm: &mut ComponentManager, id: EntityId, bytes: &[u8]) -> Result<()>
+    where T: DeserializeOwned + Component
+{
+    let components: Vec<T> = bincode::deserialize(bytes).map_err(serialization)?;
+    if cm.iter_for::<T>(id).count() == components.len() {
+        for (mut old, new) in cm.iter_for_mut::<T>(id).zip(components) {
+            *old = new;
+        }
+    } else {
+        cm.remove_for::<T>(id);
+        for component in components {
+            cm.insert(id, component);
+        }
+    }
+    Ok(())
+}
+
+fn remove<T>(cm: &mut ComponentManager, id: EntityId)
+    where T: Component
+{
+    cm.remove_for::<T>(id);
+}
+
+/// The component types to replicate. The server and its clients must
+/// register the same types in the same order.
+///
+/// # Example
+///
+/// ```rust
+/// let mut types = ReplicatedTypes::new();
+/// types.register::<Position>();
+/// types.register::<Health>();
+///
+/// let server = ReplicationServer::new(types.clone());
+/// let client = ReplicationClient::new(types);
+/// ```
+#[derive(Clone, Default)]
+pub struct ReplicatedTypes {
+    functions: Vec<Functions>,
+}
+
+impl ReplicatedTypes {
+    pub fn new() -> ReplicatedTypes
+    {
+        ReplicatedTypes::default()
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the type is already registered.
+    pub fn register<T>(&mut self)
+        where T: Serialize + DeserializeOwned + Component
+    {
+        assert!(!self.functions.iter().any(|functions| functions.type_id == TypeId::of::<T>()),
+                "component type is already replicated");
+        self.functions.push(Functions::new::<T>());
+    }
+
+    /// The number of registered types
+    pub fn len(&self) -> usize
+    {
+        self.functions.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.functions.is_empty()
+    }
+
+    fn get(&self, index: u32) -> Result<&Functions>
+    {
+        self.functions.get(index as usize)
+            .ok_or_else(|| EsError::Serialization(format!("unknown replicated type {}", index)))
+    }
+}
+
+/// Writes what changed in a server's components since the last diff, for
+/// `ReplicationClient` to apply to its mirror.
+///
+/// An entity is replicated while it holds a component of at least one
+/// replicated type: clients see it created when it gains the first and
+/// destroyed when it loses the last or is purged. Changes are found with
+/// change detection, tracking removals of every replicated type, so the
+/// server should call `forget_removed` for ticks that have been diffed, and
+/// must not call it for a tick that has not been diffed yet. Entity ids held inside components are sent
+/// as they are; clients can translate them with `ReplicationClient::client_id`.
+///
+/// # Example
+///
+/// ```rust
+/// // Once per tick, on the server
+/// let mut diff = Vec::new();
+/// server.write_diff(&mut cm, &mut diff)?;
+/// for client in clients.iter_mut() {
+///     client.connection.send(&diff);
+/// }
+/// ```
+pub struct ReplicationServer {
+    types: ReplicatedTypes,
+    /// The entities holding components of each type, as last sent
+    holders: Vec<BTreeSet<EntityId>>,
+    /// The number of replicated types each entity holds, as last sent
+    counts: HashMap<EntityId, usize>,
+    /// The change tick closed by the last diff
+    tick: u64,
+}
+
+impl ReplicationServer {
+    pub fn new(types: ReplicatedTypes) -> ReplicationServer
+    {
+        ReplicationServer {
+            holders: vec![BTreeSet::new(); types.len()],
+            types,
+            counts: HashMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Write the changes since the previous diff, closing the current change
+    /// tick of `cm`. The first diff holds every replicated component.
+    pub fn write_diff<W>(&mut self, cm: &mut ComponentManager, writer: W) -> Result<()>
+        where W: Write
+    {
+        let since = self.tick;
+        let mut changes = Vec::new();
+        let mut created = Vec::new();
+        let mut destroyed = BTreeSet::new();
+        for (index, functions) in self.types.functions.iter().enumerate() {
+            // Removals before the first diff concern entities never sent
+            cm.changes_mut().track_removals(functions.type_id);
+            let touched: BTreeSet<EntityId> = (functions.touched)(cm, since).into_iter().collect();
+            for id in touched {
+                let held = self.holders[index].contains(&id);
+                match (held, (functions.encode)(cm, id)?) {
+                    (false, Some(bytes)) => {
+                        self.holders[index].insert(id);
+                        let count = self.counts.entry(id).or_insert(0);
+                        if *count == 0 {
+                            created.push(id);
+                        }
+                        *count += 1;
+                        changes.push(Op::Added(id, index as u32, bytes));
+                    },
+                    (true, Some(bytes)) => changes.push(Op::Changed(id, index as u32, bytes)),
+                    (true, None) => {
+                        self.holders[index].remove(&id);
+                        let count = self.counts.get_mut(&id).expect("holder to be counted");
+                        *count -= 1;
+                        if *count == 0 {
+                            self.counts.remove(&id);
+                            destroyed.insert(id);
+                        }
+                        changes.push(Op::Removed(id, index as u32));
+                    },
+                    (false, None) => (),
+                }
+            }
+        }
+        self.tick = cm.increment_change_tick();
+
+        // An entity that comes and goes within one diff is never sent
+        created.retain(|id| !destroyed.remove(id));
+        let mut ops: Vec<Op> = created.into_iter().map(Op::Created).collect();
+        ops.extend(changes.into_iter().filter(|op| match *op {
+            Op::Added(id, _, _) | Op::Changed(id, _, _) | Op::Removed(id, _) =>
+                self.counts.contains_key(&id),
+            _ => true,
+        }));
+        ops.extend(destroyed.into_iter().map(Op::Destroyed));
+        bincode::serialize_into(writer, &ops).map_err(serialization)
+    }
+
+    /// Write every replicated component as of the last diff, for a client
+    /// joining part way through. Later diffs apply on top of it.
+    pub fn write_full<W>(&self, cm: &ComponentManager, writer: W) -> Result<()>
+        where W: Write
+    {
+        let mut ops: Vec<Op> = self.counts.keys().cloned().map(Op::Created).collect();
+        for (index, functions) in self.types.functions.iter().enumerate() {
+            for &id in self.holders[index].iter() {
+                if let Some(bytes) = (functions.encode)(cm, id)? {
+                    ops.push(Op::Added(id, index as u32, bytes));
+                }
+            }
+        }
+        bincode::serialize_into(writer, &ops).map_err(serialization)
+    }
+}
+
+/// Applies the diffs written by a `ReplicationServer` to a mirror of its
+/// components, creating a local entity for each replicated server entity.
+pub struct ReplicationClient {
+    types: ReplicatedTypes,
+    /// Local ids by server id
+    entities: HashMap<EntityId, EntityId>,
+    /// Server ids by local id
+    server_ids: HashMap<EntityId, EntityId>,
+}
+
+impl ReplicationClient {
+    pub fn new(types: ReplicatedTypes) -> ReplicationClient
+    {
+        ReplicationClient {
+            types,
+            entities: HashMap::new(),
+            server_ids: HashMap::new(),
+        }
+    }
+
+    /// The local entity mirroring a server entity
+    pub fn client_id(&self, server_id: EntityId) -> Option<EntityId>
+    {
+        self.entities.get(&server_id).cloned()
+    }
+
+    /// The server entity a local entity mirrors
+    pub fn server_id(&self, client_id: EntityId) -> Option<EntityId>
+    {
+        self.server_ids.get(&client_id).cloned()
+    }
+
+    /// Read a diff, or the output of `write_full`, and apply it. Diffs must
+    /// be applied in the order they were written.
+    pub fn apply<R>(&mut self, reader: R, em: &mut EntityManager, cm: &mut ComponentManager) -> Result<()>
+        where R: Read
+    {
+        let ops: Vec<Op> = bincode::deserialize_from(reader).map_err(serialization)?;
+        for op in ops {
+            match op {
+                // A server entity that is already mirrored, as when a client
+                // resyncs from `write_full`, keeps its local entity. Its
+                // replicated components are sent again after this.
+                Op::Created(server_id) => match self.client_id(server_id) {
+                    Some(id) => {
+                        for functions in self.types.functions.iter() {
+                            (functions.remove)(cm, id);
+                        }
+                    },
+                    None => {
+                        let id = em.create();
+                        self.entities.insert(server_id, id);
+                        self.server_ids.insert(id, server_id);
+                    },
+                },
+                Op::Destroyed(server_id) => {
+                    let id = self.local(server_id)?;
+                    self.entities.remove(&server_id);
+                    self.server_ids.remove(&id);
+                    em.destroy(id);
+                    cm.purge(id);
+                },
+                Op::Added(server_id, index, bytes) =>
+                    (self.types.get(index)?.insert)(cm, self.local(server_id)?, &bytes)?,
+                Op::Changed(server_id, index, bytes) =>
+                    (self.types.get(index)?.change)(cm, self.local(server_id)?, &bytes)?,
+                Op::Removed(server_id, index) =>
+                    (self.types.get(index)?.remove)(cm, self.local(server_id)?),
+            }
+        }
+        Ok(())
+    }
+
+    fn local(&self, server_id: EntityId) -> Result<EntityId>
+    {
+        self.client_id(server_id).ok_or(EsError::NoSuchEntity(server_id))
+    }
+}
@@ -0,0 +1,82 @@
+use std::any::{type_name, Any, TypeId};
+use std::collections::hash_map::HashMap;
+
+use error::{EsError, Result};
+
+/// Singleton values keyed by type: data such as the frame time or input
+/// state that belongs to no entity. Only one resource of each type is kept.
+///
+/// The resources of a `World` are kept beside its `ComponentManager` and
+/// handed to each processor, so a resource can be borrowed mutably while
+/// iterating over components.
+///
+/// # Example
+///
+/// ```rust
+/// resources.insert_resource(DeltaTime(1.0 / 60.0));
+///
+/// let dt = resources.resource::<DeltaTime>();
+/// for mut meta in cm.iter_mut::<Position>() {
+///     meta.component.x += dt.0;
+/// }
+/// ```
+#[derive(Default)]
+pub struct Resources {
+    values: HashMap<TypeId, Box<dyn Any>>,
+}
+
+impl Resources {
+    pub fn new() -> Resources
+    {
+        Resources::default()
+    }
+
+    /// Store a resource, returning the previous one of the same type
+    pub fn insert_resource<R>(&mut self, resource: R) -> Option<R>
+        where R: Any
+    {
+        self.values.insert(TypeId::of::<R>(), Box::new(resource))
+            .map(|previous| *previous.downcast::<R>().expect("downcast to R"))
+    }
+
+    pub fn remove_resource<R>(&mut self) -> Option<R>
+        where R: Any
+    {
+        self.values.remove(&TypeId::of::<R>())
+            .map(|resource| *resource.downcast::<R>().expect("downcast to R"))
+    }
+
+    pub fn has_resource<R>(&self) -> bool
+        where R: Any
+    {
+        self.values.contains_key(&TypeId::of::<R>())
+    }
+
+    pub fn resource<R>(&self) -> &R
+        where R: Any
+    {
+        self.try_resource().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_resource<R>(&self) -> Result<&R>
+        where R: Any
+    {
+        self.values.get(&TypeId::of::<R>())
+            .map(|resource| resource.downcast_ref::<R>().expect("downcast to R"))
+            .ok_or(EsError::NoSuchResource(type_name::<R>()))
+    }
+
+    pub fn resource_mut<R>(&mut self) -> &mut R
+        where R: Any
+    {
+        self.try_resource_mut().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_resource_mut<R>(&mut self) -> Result<&mut R>
+        where R: Any
+    {
+        self.values.get_mut(&TypeId::of::<R>())
+            .map(|resource| resource.downcast_mut::<R>().expect("downcast to R"))
+            .ok_or(EsError::NoSuchResource(type_name::<R>()))
+    }
+}
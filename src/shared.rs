@@ -0,0 +1,270 @@
+use std::any::{type_name, TypeId};
+use std::collections::hash_map::HashMap;
+use std::marker::PhantomData;
+use std::slice;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use component::Component;
+use entity::EntityId;
+use error::{EsError, Result};
+use storage::{Column, StorageKind, Table, Iter, IterMut, IterFor};
+
+/// Shared storage does not track changes, so every component carries the
+/// same tick
+const TICK: u64 = 0;
+
+/// A component manager that can be shared between threads, with each
+/// component type's storage behind its own lock.
+///
+/// Types are registered up front, while the manager is still owned by one
+/// thread, and must be `Send` and `Sync`. Afterwards any thread can borrow a
+/// type's storage through `read` or `write`: many readers or one writer per
+/// type, independently of every other type. An asset loader thread can then
+/// insert into one type while the main loop reads others.
+///
+/// Borrowing a type that is already borrowed incompatibly by the same thread
+/// deadlocks, as does calling `purge` while holding any guard.
+///
+/// # Example
+///
+/// ```rust
+/// let mut cm = SharedComponentManager::new();
+/// cm.register::<Mesh>(StorageKind::Dense);
+/// cm.register::<Position>(StorageKind::Dense);
+/// let cm = Arc::new(cm);
+///
+/// let loader = cm.clone();
+/// thread::spawn(move || loader.write::<Mesh>().insert(entity, load_mesh()));
+///
+/// for meta in cm.read::<Position>().iter() {
+///     draw(meta.entity, &meta.component);
+/// }
+/// ```
+pub struct SharedComponentManager {
+    storages: HashMap<TypeId, RwLock<Table>>,
+}
+
+// Safety: every table holds a single type, put there by `register`, which
+// requires it to be `Send` and `Sync`.
+unsafe impl Send for SharedComponentManager {}
+unsafe impl Sync for SharedComponentManager {}
+
+impl Default for SharedComponentManager {
+    fn default() -> SharedComponentManager
+    {
+        SharedComponentManager::new()
+    }
+}
+
+impl SharedComponentManager {
+    pub fn new() -> SharedComponentManager
+    {
+        SharedComponentManager{storages: HashMap::new()}
+    }
+
+    /// Create the storage for a component type. Registering a type again
+    /// does nothing.
+    pub fn register<T>(&mut self, kind: StorageKind)
+        where T: Component + Send + Sync
+    {
+        self.storages.entry(TypeId::of::<T>())
+            .or_insert_with(|| RwLock::new(Table::new(vec![(TypeId::of::<T>(), Box::new(Column::<T>::new()))], kind)));
+    }
+
+    pub fn is_registered<T>(&self) -> bool
+        where T: Component + Send + Sync
+    {
+        self.storages.contains_key(&TypeId::of::<T>())
+    }
+
+    fn storage<T>(&self) -> Result<&RwLock<Table>>
+        where T: Component + Send + Sync
+    {
+        self.storages.get(&TypeId::of::<T>())
+            .ok_or(EsError::NoSuchComponentType(type_name::<T>()))
+    }
+
+    /// Borrow the storage of a type for reading, blocking while it is
+    /// borrowed for writing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the type is not registered.
+    pub fn read<T>(&self) -> ReadStorage<'_, T>
+        where T: Component + Send + Sync
+    {
+        self.try_read().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_read<T>(&self) -> Result<ReadStorage<'_, T>>
+        where T: Component + Send + Sync
+    {
+        let table = self.storage::<T>()?.read().expect("component storage lock poisoned");
+        Ok(ReadStorage{table, marker: PhantomData})
+    }
+
+    /// Borrow the storage of a type for writing, blocking while it is
+    /// borrowed at all.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the type is not registered.
+    pub fn write<T>(&self) -> WriteStorage<'_, T>
+        where T: Component + Send + Sync
+    {
+        self.try_write().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_write<T>(&self) -> Result<WriteStorage<'_, T>>
+        where T: Component + Send + Sync
+    {
+        let table = self.storage::<T>()?.write().expect("component storage lock poisoned");
+        Ok(WriteStorage{table, marker: PhantomData})
+    }
+
+    /// Remove every component belonging to an entity, locking each type in
+    /// turn. Returns false if the entity had no components.
+    pub fn purge(&self, id: EntityId) -> bool
+    {
+        let mut removed = false;
+        for storage in self.storages.values() {
+            let mut table = storage.write().expect("component storage lock poisoned");
+            if let Some(row) = table.row(id) {
+                table.swap_remove_row(row);
+                removed = true;
+            }
+        }
+        removed
+    }
+}
+
+/// Shared access to the components of one type, see
+/// `SharedComponentManager::read`
+pub struct ReadStorage<'a, T> {
+    table: RwLockReadGuard<'a, Table>,
+    marker: PhantomData<T>,
+}
+
+impl<'a, T> ReadStorage<'a, T>
+    where T: Component + Send + Sync
+{
+    pub fn iter(&self) -> Iter<'_, T>
+    {
+        Iter::new(slice::from_ref(&*self.table))
+    }
+
+    pub fn iter_for(&self, id: EntityId) -> IterFor<'_, T>
+    {
+        iter_for(&self.table, id)
+    }
+
+    /// The entity's first component of type T
+    pub fn get(&self, id: EntityId) -> Option<&T>
+    {
+        self.iter_for(id).next()
+    }
+
+    pub fn contains(&self, id: EntityId) -> bool
+    {
+        self.table.row(id).is_some()
+    }
+
+    /// The number of entities with a component of the type
+    pub fn len(&self) -> usize
+    {
+        self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.len() == 0
+    }
+}
+
+/// Exclusive access to the components of one type, see
+/// `SharedComponentManager::write`
+pub struct WriteStorage<'a, T> {
+    table: RwLockWriteGuard<'a, Table>,
+    marker: PhantomData<T>,
+}
+
+impl<'a, T> WriteStorage<'a, T>
+    where T: Component + Send + Sync
+{
+    fn column_mut(&mut self) -> &mut Column<T>
+    {
+        self.table.column_mut::<T>().expect("storage to hold T")
+    }
+
+    pub fn insert(&mut self, id: EntityId, component: T)
+    {
+        match self.table.row(id) {
+            Some(row) => self.column_mut().push(row, component, TICK),
+            None => {
+                self.table.push_row(id);
+                self.column_mut().push_row(id, component, TICK);
+            },
+        }
+    }
+
+    /// Remove every component of the type from an entity, returning them in
+    /// insertion order
+    pub fn remove_for(&mut self, id: EntityId) -> Vec<T>
+    {
+        match self.table.row(id) {
+            Some(row) => self.table.take_row::<T>(row, None).0,
+            None => Vec::new(),
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T>
+    {
+        Iter::new(slice::from_ref(&*self.table))
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T>
+    {
+        IterMut::new(slice::from_mut(&mut *self.table), TICK)
+    }
+
+    pub fn iter_for(&self, id: EntityId) -> IterFor<'_, T>
+    {
+        iter_for(&self.table, id)
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&T>
+    {
+        self.iter_for(id).next()
+    }
+
+    /// Mutable access to the entity's first component of type T
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut T>
+    {
+        let row = self.table.row(id)?;
+        Some(self.column_mut().get_mut(row, TICK).into_inner())
+    }
+
+    pub fn contains(&self, id: EntityId) -> bool
+    {
+        self.table.row(id).is_some()
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.len() == 0
+    }
+}
+
+fn iter_for<T>(table: &Table, id: EntityId) -> IterFor<'_, T>
+    where T: Component + Send + Sync
+{
+    match table.row(id) {
+        Some(row) => table.column::<T>().expect("storage to hold T").iter_row(row),
+        None => IterFor::empty(),
+    }
+}
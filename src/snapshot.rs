@@ -0,0 +1,199 @@
+use std::any::TypeId;
+use std::collections::hash_map::HashMap;
+use std::collections::VecDeque;
+
+use component::{Component, ComponentManager};
+use entity::{EntityId, EntityManager};
+use error::{EsError, Result};
+use hierarchy::{Children, Parent};
+use storage::{AnyColumn, Column, Table};
+
+type CloneColumn = fn(&dyn AnyColumn) -> Box<dyn AnyColumn>;
+
+fn clone_column<T>(column: &dyn AnyColumn) -> Box<dyn AnyColumn>
+    where T: Clone + Component
+{
+    Box::new(column.as_any().downcast_ref::<Column<T>>().expect("downcast to Column<T>").clone())
+}
+
+/// How to copy the columns of each cloneable component type, see
+/// `ComponentManager::register_cloneable`
+pub(crate) struct Cloners {
+    columns: HashMap<TypeId, CloneColumn>,
+}
+
+impl Cloners {
+    pub fn new() -> Cloners
+    {
+        let mut cloners = Cloners{columns: HashMap::new()};
+        cloners.add::<Parent>();
+        cloners.add::<Children>();
+        cloners
+    }
+
+    fn add<T>(&mut self)
+        where T: Clone + Component
+    {
+        self.columns.insert(TypeId::of::<T>(), clone_column::<T>);
+    }
+
+    fn clone_column(&self, type_id: TypeId, column: &dyn AnyColumn) -> Result<Box<dyn AnyColumn>>
+    {
+        match self.columns.get(&type_id) {
+            Some(clone) => Ok(clone(column)),
+            None => Err(EsError::NotCloneable(column.type_name())),
+        }
+    }
+
+    /// Copy a table. Empty tables are copied whatever their types, as
+    /// there are no components to clone.
+    pub fn clone_table(&self, table: &Table) -> Result<Table>
+    {
+        if table.len() == 0 {
+            return table.try_clone(|_, column| Ok(column.empty()));
+        }
+        table.try_clone(|type_id, column| self.clone_column(type_id, column))
+    }
+}
+
+/// A copy of every table of a `ComponentManager` along with the indexes
+/// into them
+pub(crate) struct Storage {
+    pub tables: Vec<Table>,
+    pub table_index: HashMap<Vec<TypeId>, usize>,
+    pub type_tables: HashMap<TypeId, Vec<usize>>,
+    pub locations: HashMap<EntityId, usize>,
+}
+
+impl ComponentManager {
+    /// Allow components of type T to be copied into a `Snapshot`. `Parent`
+    /// and `Children` are always cloneable.
+    pub fn register_cloneable<T>(&mut self)
+        where T: Clone + Component
+    {
+        self.cloners_mut().add::<T>();
+    }
+
+    /// Whether components of type T can be copied into a `Snapshot`
+    pub fn is_cloneable<T>(&self) -> bool
+        where T: 'static
+    {
+        self.cloners().columns.contains_key(&TypeId::of::<T>())
+    }
+}
+
+/// The entities and components of a `World` at the start of a tick, see
+/// `World::snapshot`.
+///
+/// Only the state of entities and their components is captured. Resources,
+/// events, hooks and processors are left as they are by `World::restore`,
+/// as is the change tick.
+pub struct Snapshot {
+    tick: u64,
+    entities: EntityManager,
+    storage: Storage,
+}
+
+impl Snapshot {
+    pub(crate) fn new(tick: u64, entities: EntityManager, storage: Storage) -> Snapshot
+    {
+        Snapshot{tick, entities, storage}
+    }
+
+    /// The tick the world was about to run when the snapshot was taken
+    pub fn tick(&self) -> u64
+    {
+        self.tick
+    }
+
+    pub(crate) fn entities(&self) -> &EntityManager
+    {
+        &self.entities
+    }
+
+    pub(crate) fn storage(&self) -> &Storage
+    {
+        &self.storage
+    }
+}
+
+/// A ring buffer of the last few snapshots, for rolling back to an earlier
+/// tick. Pushing onto a full buffer drops the oldest snapshot.
+///
+/// # Example
+///
+/// ```rust
+/// let mut snapshots = Snapshots::new(8);
+/// loop {
+///     if let Some(tick) = network.earliest_late_input() {
+///         world.restore(snapshots.get(tick).expect("input within 8 ticks"));
+///         snapshots.discard_after(tick);
+///     }
+///     snapshots.push(world.snapshot());
+///     world.tick();
+/// }
+/// ```
+pub struct Snapshots {
+    snapshots: VecDeque<Snapshot>,
+    capacity: usize,
+}
+
+impl Snapshots {
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Snapshots
+    {
+        assert!(capacity > 0, "snapshot buffer must hold at least one snapshot");
+        Snapshots{snapshots: VecDeque::with_capacity(capacity), capacity}
+    }
+
+    /// The number of snapshots kept at most
+    pub fn capacity(&self) -> usize
+    {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.snapshots.is_empty()
+    }
+
+    /// Keep a snapshot, dropping the oldest one if the buffer is full
+    pub fn push(&mut self, snapshot: Snapshot)
+    {
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// The snapshot taken at the start of `tick`, if it is still kept
+    pub fn get(&self, tick: u64) -> Option<&Snapshot>
+    {
+        self.snapshots.iter().rev().find(|snapshot| snapshot.tick == tick)
+    }
+
+    /// The most recently pushed snapshot
+    pub fn latest(&self) -> Option<&Snapshot>
+    {
+        self.snapshots.back()
+    }
+
+    /// Drop the snapshots of ticks after `tick`, which no longer happened
+    /// once the world has been rolled back to it
+    pub fn discard_after(&mut self, tick: u64)
+    {
+        self.snapshots.retain(|snapshot| snapshot.tick <= tick);
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.snapshots.clear();
+    }
+}
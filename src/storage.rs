@@ -0,0 +1,646 @@
+use std::collections::hash_map::HashMap;
+use std::any::{type_name, Any, TypeId};
+use std::iter;
+use std::mem;
+use std::slice;
+
+use change::{Mut, Ticks};
+use error::Result;
+use {Component, EntityId, EntityMeta};
+
+type ColumnIter<'a, T> = iter::Chain<slice::Iter<'a, EntityMeta<T>>,
+                                     iter::Flatten<slice::Iter<'a, Vec<EntityMeta<T>>>>>;
+/// The first entries of every row followed by the extra ones
+type RowsMut<'a, T> = iter::Chain<slice::IterMut<'a, T>, iter::Flatten<slice::IterMut<'a, Vec<T>>>>;
+
+/// Every component of a single type within a `Table`, one row per entity.
+///
+/// The first component of each row is kept packed and aligned with the
+/// table's rows so joins can walk several columns in step. Any further
+/// components of the same type on that entity live in `extra`, which stays
+/// empty (and unallocated) in the common case. Each component's `Ticks` are
+/// kept alongside it in `first_ticks` and `extra_ticks`.
+#[derive(Clone)]
+pub struct Column<T> {
+    first: Vec<EntityMeta<T>>,
+    extra: Vec<Vec<EntityMeta<T>>>,
+    first_ticks: Vec<Ticks>,
+    extra_ticks: Vec<Vec<Ticks>>,
+}
+
+impl<T> Column<T> {
+    pub fn new() -> Column<T>
+    {
+        Column {
+            first: Vec::new(),
+            extra: Vec::new(),
+            first_ticks: Vec::new(),
+            extra_ticks: Vec::new(),
+        }
+    }
+
+    /// Start a new row. Must be called in step with `Table::push_row`.
+    pub fn push_row(&mut self, id: EntityId, component: T, tick: u64)
+    {
+        self.first.push(EntityMeta{entity: id, component});
+        self.extra.push(Vec::new());
+        self.first_ticks.push(Ticks::new(tick));
+        self.extra_ticks.push(Vec::new());
+    }
+
+    /// Add another component to an existing row
+    pub fn push(&mut self, row: usize, component: T, tick: u64)
+    {
+        let entity = self.first[row].entity;
+        self.extra[row].push(EntityMeta{entity, component});
+        self.extra_ticks[row].push(Ticks::new(tick));
+    }
+
+    /// The number of components in the row
+    pub fn count(&self, row: usize) -> usize
+    {
+        1 + self.extra[row].len()
+    }
+
+    pub fn get(&self, row: usize) -> &T
+    {
+        &self.first[row].component
+    }
+
+    /// The row's first component, marked changed at `tick` once written
+    pub fn get_mut(&mut self, row: usize, tick: u64) -> Mut<'_, T>
+    {
+        Mut::new(&mut self.first[row].component, &mut self.first_ticks[row], tick)
+    }
+
+    /// The ticks of every component in the row
+    pub fn ticks(&self, row: usize) -> iter::Chain<slice::Iter<'_, Ticks>, slice::Iter<'_, Ticks>>
+    {
+        self.first_ticks[row..row + 1].iter().chain(self.extra_ticks[row].iter())
+    }
+
+    /// Pointer to the row's first component.
+    ///
+    /// Unlike `get_mut` this never borrows the whole column, so it may be
+    /// called while references into other rows are alive.
+    pub unsafe fn first_ptr(column: *mut Column<T>, row: usize) -> *mut T
+    {
+        let meta = (*column).first.as_mut_ptr().add(row);
+        &mut (*meta).component
+    }
+
+    /// Pointer to the ticks of the row's first component, see `first_ptr`
+    pub unsafe fn first_ticks_ptr(column: *mut Column<T>, row: usize) -> *mut Ticks
+    {
+        (*column).first_ticks.as_mut_ptr().add(row)
+    }
+
+    pub fn iter(&self) -> ColumnIter<'_, T>
+    {
+        self.first.iter().chain(self.extra.iter().flatten())
+    }
+
+    /// Iterate mutably, marking each component changed at `tick` once it is
+    /// written
+    pub fn iter_mut(&mut self, tick: u64) -> ColumnIterMut<'_, T>
+    {
+        ColumnIterMut {
+            metas: self.first.iter_mut().chain(self.extra.iter_mut().flatten()),
+            ticks: self.first_ticks.iter_mut().chain(self.extra_ticks.iter_mut().flatten()),
+            tick,
+        }
+    }
+
+    pub fn iter_row(&self, row: usize) -> IterFor<'_, T>
+    {
+        IterFor {
+            first: Some(&self.first[row].component),
+            extra: self.extra[row].iter(),
+        }
+    }
+
+    /// Iterate over the row mutably, marking each component changed at
+    /// `tick` once it is written
+    pub fn iter_row_mut(&mut self, row: usize, tick: u64) -> IterForMut<'_, T>
+    {
+        IterForMut {
+            first: Some(Mut::new(&mut self.first[row].component, &mut self.first_ticks[row], tick)),
+            extra: self.extra[row].iter_mut().zip(self.extra_ticks[row].iter_mut()),
+            tick,
+        }
+    }
+
+    /// Remove the row's component at `position`. The row must hold more than
+    /// one component, as rows are never left empty.
+    pub fn remove_one(&mut self, row: usize, position: usize) -> T
+    {
+        debug_assert!(self.count(row) > 1);
+        if position == 0 {
+            self.first_ticks[row] = self.extra_ticks[row].remove(0);
+            let next = self.extra[row].remove(0);
+            mem::replace(&mut self.first[row], next).component
+        } else {
+            self.extra_ticks[row].remove(position - 1);
+            self.extra[row].remove(position - 1).component
+        }
+    }
+
+    /// Remove a whole row, moving the last row into its place. Returns the
+    /// row's components in insertion order.
+    pub fn take_row(&mut self, row: usize) -> Vec<T>
+    {
+        self.first_ticks.swap_remove(row);
+        self.extra_ticks.swap_remove(row);
+        let first = self.first.swap_remove(row);
+        let extra = self.extra.swap_remove(row);
+        iter::once(first).chain(extra)
+            .map(|meta| meta.component)
+            .collect()
+    }
+}
+
+/// Mutable iterator over every component of a column, see `Column::iter_mut`
+pub struct ColumnIterMut<'a, T: 'a> {
+    metas: RowsMut<'a, EntityMeta<T>>,
+    ticks: RowsMut<'a, Ticks>,
+    tick: u64,
+}
+
+impl<'a, T> Iterator for ColumnIterMut<'a, T> {
+    type Item = Mut<'a, EntityMeta<T>>;
+
+    fn next(&mut self) -> Option<Mut<'a, EntityMeta<T>>>
+    {
+        let meta = self.metas.next()?;
+        let ticks = self.ticks.next().expect("ticks for every component");
+        Some(Mut::new(meta, ticks, self.tick))
+    }
+}
+
+/// Type erased operations over a `Column<T>`
+pub trait AnyColumn {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    /// The name of the component type
+    fn type_name(&self) -> &'static str;
+
+    /// A new, empty column for the same component type
+    fn empty(&self) -> Box<dyn AnyColumn>;
+
+    /// The components in a row, in insertion order
+    fn row_components(&self, row: usize) -> Vec<&dyn Any>;
+
+    /// The row's first component, marked changed at `tick`
+    fn first_mut(&mut self, row: usize, tick: u64) -> &mut dyn Any;
+
+    /// Drop a row, moving the last row into its place
+    fn swap_remove_row(&mut self, row: usize);
+
+    /// Move a row onto the end of `dst`, which must hold the same component
+    /// type, moving the last row into its place
+    fn move_row(&mut self, row: usize, dst: &mut dyn AnyColumn);
+}
+
+impl<T> AnyColumn for Column<T>
+    where T: Component
+{
+    fn as_any(&self) -> &dyn Any
+    {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any
+    {
+        self
+    }
+
+    fn type_name(&self) -> &'static str
+    {
+        type_name::<T>()
+    }
+
+    fn empty(&self) -> Box<dyn AnyColumn>
+    {
+        Box::new(Column::<T>::new())
+    }
+
+    fn row_components(&self, row: usize) -> Vec<&dyn Any>
+    {
+        self.iter_row(row).map(|component| component as &dyn Any).collect()
+    }
+
+    fn first_mut(&mut self, row: usize, tick: u64) -> &mut dyn Any
+    {
+        self.get_mut(row, tick).into_inner()
+    }
+
+    fn swap_remove_row(&mut self, row: usize)
+    {
+        self.take_row(row);
+    }
+
+    fn move_row(&mut self, row: usize, dst: &mut dyn AnyColumn)
+    {
+        let dst = dst.as_any_mut()
+            .downcast_mut::<Column<T>>()
+            .expect("downcast to Column<T>");
+        dst.first.push(self.first.swap_remove(row));
+        dst.extra.push(self.extra.swap_remove(row));
+        dst.first_ticks.push(self.first_ticks.swap_remove(row));
+        dst.extra_ticks.push(self.extra_ticks.swap_remove(row));
+    }
+}
+
+/// How the components of one type are stored, see
+/// `ComponentManager::register`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StorageKind {
+    /// Packed columns found by hashing the entity id. When the manager uses
+    /// archetypes these columns are shared with the entity's other dense
+    /// components, so joins walk them in step but adding or removing one
+    /// moves the entity's whole row.
+    Dense,
+    /// Packed columns of their own, found through an array indexed by the
+    /// entity's slot. Insert, remove and lookup never hash and never move
+    /// other components, at the cost of memory proportional to the highest
+    /// entity index seen. Suits tags and short lived status effects.
+    SparseSet,
+    /// Packed columns of their own, found by hashing the entity id. Like
+    /// `SparseSet` without the memory cost for types few entities have.
+    HashMap,
+}
+
+/// Finds the row of an entity within a table
+#[derive(Clone)]
+enum RowIndex {
+    Hashed(HashMap<EntityId, usize>),
+    /// Rows by entity index, along with the entity holding each slot. An
+    /// entity whose slot is held by another generation of it is found by
+    /// hashing instead, so stale and fresh ids never hide each other's rows.
+    Sparse(Vec<Option<(EntityId, usize)>>, HashMap<EntityId, usize>),
+}
+
+/// A set of entities sharing the same component types, one column per type.
+///
+/// Rows are kept packed: removing a row moves the last row into its place.
+pub struct Table {
+    types: Vec<TypeId>,
+    entities: Vec<EntityId>,
+    rows: RowIndex,
+    columns: HashMap<TypeId, Box<dyn AnyColumn>>,
+}
+
+impl Table {
+    /// Create a table over the given columns. `SparseSet` tables find rows
+    /// by entity index, any other kind by hashing.
+    pub fn new(columns: Vec<(TypeId, Box<dyn AnyColumn>)>, kind: StorageKind) -> Table
+    {
+        let mut types: Vec<TypeId> = columns.iter().map(|&(type_id, _)| type_id).collect();
+        types.sort();
+        let rows = match kind {
+            StorageKind::SparseSet => RowIndex::Sparse(Vec::new(), HashMap::new()),
+            StorageKind::Dense | StorageKind::HashMap => RowIndex::Hashed(HashMap::new()),
+        };
+        Table {
+            types,
+            entities: Vec::new(),
+            rows,
+            columns: columns.into_iter().collect(),
+        }
+    }
+
+    /// The component types of this table, sorted
+    pub fn types(&self) -> &[TypeId]
+    {
+        &self.types
+    }
+
+    pub fn has(&self, type_id: TypeId) -> bool
+    {
+        self.columns.contains_key(&type_id)
+    }
+
+    pub fn entities(&self) -> &[EntityId]
+    {
+        &self.entities
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.entities.len()
+    }
+
+    pub fn row(&self, id: EntityId) -> Option<usize>
+    {
+        match self.rows {
+            RowIndex::Hashed(ref rows) => rows.get(&id).cloned(),
+            RowIndex::Sparse(ref rows, ref others) => match rows.get(id.index() as usize) {
+                Some(&Some((holder, row))) if holder == id => Some(row),
+                _ => others.get(&id).cloned(),
+            },
+        }
+    }
+
+    fn set_row(&mut self, id: EntityId, row: usize)
+    {
+        match self.rows {
+            RowIndex::Hashed(ref mut rows) => {
+                rows.insert(id, row);
+            },
+            RowIndex::Sparse(ref mut rows, ref mut others) => {
+                if let Some(other) = others.get_mut(&id) {
+                    *other = row;
+                    return;
+                }
+                let index = id.index() as usize;
+                if index >= rows.len() {
+                    rows.resize(index + 1, None);
+                }
+                match rows[index] {
+                    Some((holder, _)) if holder != id => {
+                        others.insert(id, row);
+                    },
+                    _ => rows[index] = Some((id, row)),
+                }
+            },
+        }
+    }
+
+    fn unset_row(&mut self, id: EntityId)
+    {
+        match self.rows {
+            RowIndex::Hashed(ref mut rows) => {
+                rows.remove(&id);
+            },
+            RowIndex::Sparse(ref mut rows, ref mut others) => {
+                let slot = &mut rows[id.index() as usize];
+                if slot.is_some_and(|(holder, _)| holder == id) {
+                    *slot = None;
+                } else {
+                    others.remove(&id);
+                }
+            },
+        }
+    }
+
+    pub fn column<T>(&self) -> Option<&Column<T>>
+        where T: 'static
+    {
+        self.columns.get(&TypeId::of::<T>())
+            .map(|column| column.as_any().downcast_ref::<Column<T>>()
+                .expect("downcast to Column<T>"))
+    }
+
+    pub fn column_mut<T>(&mut self) -> Option<&mut Column<T>>
+        where T: 'static
+    {
+        self.columns.get_mut(&TypeId::of::<T>())
+            .map(|column| column.as_any_mut().downcast_mut::<Column<T>>()
+                .expect("downcast to Column<T>"))
+    }
+
+    /// The name of a component type the table holds
+    pub fn type_name(&self, type_id: TypeId) -> Option<&'static str>
+    {
+        self.columns.get(&type_id).map(|column| column.type_name())
+    }
+
+    /// The components of one type in a row, in insertion order
+    pub fn row_components(&self, type_id: TypeId, row: usize) -> Vec<&dyn Any>
+    {
+        self.columns.get(&type_id).map_or(Vec::new(), |column| column.row_components(row))
+    }
+
+    /// The first component of one type in a row, marked changed at `tick`
+    pub fn first_component_mut(&mut self, type_id: TypeId, row: usize, tick: u64) -> Option<&mut dyn Any>
+    {
+        self.columns.get_mut(&type_id).map(|column| column.first_mut(row, tick))
+    }
+
+    /// Take the column of a type out of the table, leaving the table without
+    /// it until it is put back with `restore_column`
+    pub fn take_column(&mut self, type_id: TypeId) -> Option<Box<dyn AnyColumn>>
+    {
+        self.columns.remove(&type_id)
+    }
+
+    pub fn restore_column(&mut self, type_id: TypeId, column: Box<dyn AnyColumn>)
+    {
+        debug_assert!(self.types.contains(&type_id));
+        self.columns.insert(type_id, column);
+    }
+
+    /// A copy of the table, copying each column with `clone_column`
+    pub fn try_clone<F>(&self, clone_column: F) -> Result<Table>
+        where F: Fn(TypeId, &dyn AnyColumn) -> Result<Box<dyn AnyColumn>>
+    {
+        let mut columns = HashMap::with_capacity(self.columns.len());
+        for (&type_id, column) in self.columns.iter() {
+            columns.insert(type_id, clone_column(type_id, &**column)?);
+        }
+        Ok(Table {
+            types: self.types.clone(),
+            entities: self.entities.clone(),
+            rows: self.rows.clone(),
+            columns,
+        })
+    }
+
+    /// New, empty columns of the same types as this table
+    pub fn empty_columns(&self) -> Vec<(TypeId, Box<dyn AnyColumn>)>
+    {
+        self.columns.iter()
+            .map(|(&type_id, column)| (type_id, column.empty()))
+            .collect()
+    }
+
+    /// Reserve a row for the entity. The caller must push a component onto
+    /// every column to complete it.
+    pub fn push_row(&mut self, id: EntityId) -> usize
+    {
+        let row = self.entities.len();
+        self.entities.push(id);
+        self.set_row(id, row);
+        row
+    }
+
+    /// Forget a row whose column entries are already gone, moving the last
+    /// row into its place
+    fn forget_row(&mut self, row: usize)
+    {
+        let id = self.entities.swap_remove(row);
+        self.unset_row(id);
+        if row < self.entities.len() {
+            let moved = self.entities[row];
+            self.set_row(moved, row);
+        }
+    }
+
+    /// Drop a row along with all of its components
+    pub fn swap_remove_row(&mut self, row: usize)
+    {
+        for column in self.columns.values_mut() {
+            column.swap_remove_row(row);
+        }
+        self.forget_row(row);
+    }
+
+    /// Drop every row
+    pub fn clear(&mut self)
+    {
+        while !self.entities.is_empty() {
+            let last = self.entities.len() - 1;
+            self.swap_remove_row(last);
+        }
+    }
+
+    /// Move a row into `dst`, returning its row there. Columns that `dst`
+    /// lacks are dropped; columns that only `dst` has must be filled in by
+    /// the caller.
+    pub fn move_row(&mut self, row: usize, dst: &mut Table) -> usize
+    {
+        self.transfer_row(row, Some(dst), None)
+            .expect("row to be moved into dst")
+    }
+
+    /// Remove a row's components of type T and return them. The rest of the
+    /// row moves into `dst` when given, as with `move_row`, and is dropped
+    /// otherwise.
+    pub fn take_row<T>(&mut self, row: usize, dst: Option<&mut Table>) -> (Vec<T>, Option<usize>)
+        where T: 'static
+    {
+        let taken = self.column_mut::<T>()
+            .expect("table to hold a column for T")
+            .take_row(row);
+        (taken, self.transfer_row(row, dst, Some(TypeId::of::<T>())))
+    }
+
+    fn transfer_row(&mut self, row: usize, dst: Option<&mut Table>, taken: Option<TypeId>) -> Option<usize>
+    {
+        let id = self.entities[row];
+        let mut dst = dst;
+        for (type_id, column) in self.columns.iter_mut() {
+            if Some(*type_id) == taken {
+                continue;
+            }
+            match dst.as_mut().and_then(|dst| dst.columns.get_mut(type_id)) {
+                Some(dst_column) => column.move_row(row, &mut **dst_column),
+                None => column.swap_remove_row(row),
+            }
+        }
+        self.forget_row(row);
+        dst.map(|dst| dst.push_row(id))
+    }
+}
+
+/// Iterator over every component of one type, see `ComponentManager::iter`
+pub struct Iter<'a, T: 'a> {
+    tables: slice::Iter<'a, Table>,
+    current: Option<ColumnIter<'a, T>>,
+}
+
+impl<'a, T> Iter<'a, T> {
+    pub(crate) fn new(tables: &'a [Table]) -> Iter<'a, T>
+    {
+        Iter{tables: tables.iter(), current: None}
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T>
+    where T: 'static
+{
+    type Item = &'a EntityMeta<T>;
+
+    fn next(&mut self) -> Option<&'a EntityMeta<T>>
+    {
+        loop {
+            if let Some(meta) = self.current.as_mut().and_then(|current| current.next()) {
+                return Some(meta);
+            }
+            self.current = self.tables.next()?.column::<T>().map(|column| column.iter());
+        }
+    }
+}
+
+/// Mutable iterator over every component of one type, see
+/// `ComponentManager::iter_mut`
+pub struct IterMut<'a, T: 'a> {
+    tables: slice::IterMut<'a, Table>,
+    current: Option<ColumnIterMut<'a, T>>,
+    tick: u64,
+}
+
+impl<'a, T> IterMut<'a, T> {
+    pub(crate) fn new(tables: &'a mut [Table], tick: u64) -> IterMut<'a, T>
+    {
+        IterMut{tables: tables.iter_mut(), current: None, tick}
+    }
+}
+
+impl<'a, T> Iterator for IterMut<'a, T>
+    where T: 'static
+{
+    type Item = Mut<'a, EntityMeta<T>>;
+
+    fn next(&mut self) -> Option<Mut<'a, EntityMeta<T>>>
+    {
+        loop {
+            if let Some(meta) = self.current.as_mut().and_then(|current| current.next()) {
+                return Some(meta);
+            }
+            let tick = self.tick;
+            self.current = self.tables.next()?.column_mut::<T>().map(|column| column.iter_mut(tick));
+        }
+    }
+}
+
+/// Iterator over one entity's components of one type, see
+/// `ComponentManager::iter_for`
+pub struct IterFor<'a, T: 'a> {
+    first: Option<&'a T>,
+    extra: slice::Iter<'a, EntityMeta<T>>,
+}
+
+impl<'a, T> IterFor<'a, T> {
+    pub(crate) fn empty() -> IterFor<'a, T>
+    {
+        IterFor{first: None, extra: [].iter()}
+    }
+}
+
+impl<'a, T> Iterator for IterFor<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T>
+    {
+        self.first.take()
+            .or_else(|| self.extra.next().map(|meta| &meta.component))
+    }
+}
+
+/// Mutable iterator over one entity's components of one type, see
+/// `ComponentManager::iter_for_mut`
+pub struct IterForMut<'a, T: 'a> {
+    first: Option<Mut<'a, T>>,
+    extra: iter::Zip<slice::IterMut<'a, EntityMeta<T>>, slice::IterMut<'a, Ticks>>,
+    tick: u64,
+}
+
+impl<'a, T> IterForMut<'a, T> {
+    pub(crate) fn empty() -> IterForMut<'a, T>
+    {
+        IterForMut{first: None, extra: [].iter_mut().zip([].iter_mut()), tick: 0}
+    }
+}
+
+impl<'a, T> Iterator for IterForMut<'a, T> {
+    type Item = Mut<'a, T>;
+
+    fn next(&mut self) -> Option<Mut<'a, T>>
+    {
+        let tick = self.tick;
+        self.first.take()
+            .or_else(|| self.extra.next().map(|(meta, ticks)| Mut::new(&mut meta.component, ticks, tick)))
+    }
+}
@@ -0,0 +1,282 @@
+use std::any::Any;
+
+use builder::EntityBuilder;
+use bundle::Bundle;
+use commands::Commands;
+use entity::{EntityId, EntityManager};
+use component::ComponentManager;
+use events::EventBus;
+use parallel::{self, Access, ParallelProcessor};
+use processor::Processor;
+use error::Result;
+use resources::Resources;
+use snapshot::Snapshot;
+
+enum Job {
+    /// Runs on its own, with full access to the world
+    Exclusive(Box<dyn Processor>),
+    /// May run alongside other parallel processors it does not conflict with
+    Parallel(Box<dyn ParallelProcessor>, Access),
+}
+
+struct Scheduled {
+    job: Job,
+    interval: u64,
+}
+
+impl Scheduled {
+    fn conflicts(&self, other: &Scheduled) -> bool
+    {
+        match (&self.job, &other.job) {
+            (Job::Parallel(_, a), Job::Parallel(_, b)) => a.conflicts(b),
+            _ => true,
+        }
+    }
+
+    fn commands(&mut self) -> Option<&mut Commands>
+    {
+        match self.job {
+            Job::Exclusive(ref mut processor) => processor.commands(),
+            Job::Parallel(ref mut processor, _) => processor.commands(),
+        }
+    }
+}
+
+/// Owns the entity and component managers, the resources and event bus
+/// beside them, and the processors that operate on them all.
+pub struct World {
+    entities: EntityManager,
+    components: ComponentManager,
+    resources: Resources,
+    events: EventBus,
+    processors: Vec<Scheduled>,
+    tick: u64,
+}
+
+impl Default for World {
+    fn default() -> World
+    {
+        World::new()
+    }
+}
+
+impl World {
+    pub fn new() -> World
+    {
+        World {
+            entities: EntityManager::new(),
+            components: ComponentManager::new(),
+            resources: Resources::new(),
+            events: EventBus::new(),
+            processors: Vec::new(),
+            tick: 0,
+        }
+    }
+
+    pub fn entities(&self) -> &EntityManager
+    {
+        &self.entities
+    }
+
+    pub fn entities_mut(&mut self) -> &mut EntityManager
+    {
+        &mut self.entities
+    }
+
+    pub fn components(&self) -> &ComponentManager
+    {
+        &self.components
+    }
+
+    pub fn components_mut(&mut self) -> &mut ComponentManager
+    {
+        &mut self.components
+    }
+
+    pub fn resources(&self) -> &Resources
+    {
+        &self.resources
+    }
+
+    pub fn resources_mut(&mut self) -> &mut Resources
+    {
+        &mut self.resources
+    }
+
+    /// The event bus processors use to talk to each other, see `EventBus`
+    pub fn events(&self) -> &EventBus
+    {
+        &self.events
+    }
+
+    pub fn events_mut(&mut self) -> &mut EventBus
+    {
+        &mut self.events
+    }
+
+    /// Start composing a new entity, see `EntityBuilder`
+    pub fn build_entity(&mut self) -> EntityBuilder<'_>
+    {
+        EntityBuilder::new(&mut self.entities, &mut self.components)
+    }
+
+    /// Create an entity for each bundle of components, returning their ids
+    /// in order.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let bullets = world.spawn_batch((0..100).map(|i| (Position{x: i, y: 0}, Velocity{x: 0, y: 5})));
+    /// ```
+    pub fn spawn_batch<B, I>(&mut self, bundles: I) -> Vec<EntityId>
+        where B: Bundle, I: IntoIterator<Item = B>
+    {
+        bundles.into_iter()
+            .map(|bundle| {
+                let id = self.entities.create();
+                self.components.insert_bundle(id, bundle);
+                id
+            })
+            .collect()
+    }
+
+    /// Store a resource, see `Resources`
+    pub fn insert_resource<R>(&mut self, resource: R) -> Option<R>
+        where R: Any
+    {
+        self.resources.insert_resource(resource)
+    }
+
+    pub fn resource<R>(&self) -> &R
+        where R: Any
+    {
+        self.resources.resource()
+    }
+
+    pub fn resource_mut<R>(&mut self) -> &mut R
+        where R: Any
+    {
+        self.resources.resource_mut()
+    }
+
+    /// Copy the entities and components of the world, to `restore` later.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any component's type is not registered with
+    /// `ComponentManager::register_cloneable`.
+    pub fn snapshot(&self) -> Snapshot
+    {
+        self.try_snapshot().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Copy the entities and components of the world, see `Snapshot`. The
+    /// components are cloned rather than serialized, so every type must be
+    /// registered with `ComponentManager::register_cloneable`.
+    pub fn try_snapshot(&self) -> Result<Snapshot>
+    {
+        let storage = self.components.clone_storage()?;
+        Ok(Snapshot::new(self.tick, self.entities.clone(), storage))
+    }
+
+    /// Put the entities and components back as they were when the snapshot
+    /// was taken, and rewind to its tick so the ticks after it can be run
+    /// again. Hooks are not run for the components that come and go.
+    pub fn restore(&mut self, snapshot: &Snapshot)
+    {
+        self.entities = snapshot.entities().clone();
+        self.components.restore_storage(snapshot.storage());
+        self.tick = snapshot.tick();
+    }
+
+    /// Destroy an entity along with all of its descendants, see
+    /// `ComponentManager::destroy_subtree`
+    pub fn destroy_subtree(&mut self, root: EntityId) -> Vec<EntityId>
+    {
+        self.components.destroy_subtree(&mut self.entities, root)
+    }
+
+    /// Register a processor that runs once every `interval` game-ticks,
+    /// starting with the first tick. Processors run in the order they were
+    /// added.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut world = World::new();
+    /// world.add_processor(Physics, 10);
+    /// world.add_processor(Rendering, 1);
+    /// ```
+    pub fn add_processor<P>(&mut self, processor: P, interval: u64)
+        where P: Processor+'static
+    {
+        self.schedule(Job::Exclusive(Box::new(processor)), interval);
+    }
+
+    /// Register a processor that may run at the same time as other parallel
+    /// processors, on a thread pool, once every `interval` game-ticks.
+    ///
+    /// Each tick the due processors are split into stages. A processor whose
+    /// `Access` conflicts with an earlier one, and any `Processor` added with
+    /// `add_processor`, waits for that one to finish, so the order they were
+    /// added in is kept wherever it matters.
+    pub fn add_parallel_processor<P>(&mut self, processor: P, interval: u64)
+        where P: ParallelProcessor+'static
+    {
+        let access = processor.access();
+        self.schedule(Job::Parallel(Box::new(processor), access), interval);
+    }
+
+    fn schedule(&mut self, job: Job, interval: u64)
+    {
+        assert!(interval > 0, "processor interval must be at least one tick");
+        self.processors.push(Scheduled{job, interval});
+    }
+
+    /// The number of ticks run so far
+    pub fn current_tick(&self) -> u64
+    {
+        self.tick
+    }
+
+    /// Run a single game-tick, invoking every processor that is due and then
+    /// updating the event bus. Processors' `Commands` are applied as each
+    /// stage finishes.
+    pub fn tick(&mut self)
+    {
+        let tick = self.tick;
+        let due: Vec<usize> = (0..self.processors.len())
+            .filter(|&i| tick.is_multiple_of(self.processors[i].interval))
+            .collect();
+        let stages = parallel::stages(due.len(), |a, b| {
+            self.processors[due[a]].conflicts(&self.processors[due[b]])
+        });
+
+        for stage in stages {
+            let stage: Vec<usize> = stage.into_iter().map(|i| due[i]).collect();
+            let mut parallel = Vec::new();
+            for (i, scheduled) in self.processors.iter_mut().enumerate() {
+                if !stage.contains(&i) {
+                    continue;
+                }
+                match scheduled.job {
+                    Job::Exclusive(ref mut processor) =>
+                        processor.process(&mut self.entities, &mut self.components,
+                                          &mut self.resources, &mut self.events),
+                    Job::Parallel(ref mut processor, ref access) =>
+                        parallel.push((processor, access)),
+                }
+            }
+            if !parallel.is_empty() {
+                parallel::run(parallel, &self.entities, &mut self.components, &self.resources);
+            }
+            for &i in stage.iter() {
+                if let Some(commands) = self.processors[i].commands() {
+                    commands.apply(&mut self.entities, &mut self.components);
+                }
+            }
+        }
+        self.events.update();
+        self.tick += 1;
+    }
+}
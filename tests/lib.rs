@@ -2,7 +2,7 @@ extern crate entity_system;
 
 mod test_entity_manager {
     extern crate entity_system;
-    use entity_system::EntityManager;
+    use entity_system::{EntityManager, EsError, NamePolicy};
 
     #[test]
     fn new_entities_are_unique() {
@@ -26,10 +26,85 @@ mod test_entity_manager {
         assert_eq!(entity, result);
         assert_eq!(entity2, result2)
     }
+
+    #[test]
+    fn destroyed_entities_are_recycled_with_new_generation() {
+        let mut em = EntityManager::new();
+        let entity = em.create();
+        let entity2 = em.create();
+        assert!(em.is_alive(entity));
+
+        assert!(em.destroy(entity));
+        assert!(!em.is_alive(entity));
+        assert!(em.is_alive(entity2));
+        assert!(!em.destroy(entity), "Destroying a dead entity should return false");
+
+        let recycled = em.create();
+        assert_eq!(entity.index(), recycled.index());
+        assert!(entity.generation() != recycled.generation());
+        assert!(entity != recycled);
+        assert!(em.is_alive(recycled));
+        assert!(!em.is_alive(entity), "Stale id should not match the recycled slot");
+    }
+
+    #[test]
+    fn destroying_named_entity_forgets_name() {
+        let mut em = EntityManager::new();
+        let entity = em.create_named("One");
+        em.destroy(entity);
+
+        assert!(em.get_named("One").is_err());
+    }
+
+    #[test]
+    fn missing_name_is_an_error() {
+        let em = EntityManager::new();
+        assert_eq!(em.get_named("Nobody"), Err(EsError::NoSuchNamedEntity("Nobody".to_string())));
+    }
+
+    #[test]
+    fn names_can_change_at_runtime() {
+        let mut em = EntityManager::new();
+        let goblin = em.create_named(format!("goblin-{}", 1));
+        let chest = em.create();
+        assert_eq!(em.name_of(goblin), Some("goblin-1"));
+        assert_eq!(em.name_of(chest), None);
+
+        em.rename(chest, "chest").unwrap();
+        em.rename(goblin, "boss").unwrap();
+        assert!(em.get_named("goblin-1").is_err());
+        assert_eq!(em.get_named("boss"), Ok(goblin));
+
+        let mut names: Vec<_> = em.names().collect();
+        names.sort();
+        assert_eq!(names, vec![("boss", goblin), ("chest", chest)]);
+
+        assert_eq!(em.unname(chest), Some("chest".to_string()));
+        assert_eq!(em.unname(chest), None);
+        assert!(em.get_named("chest").is_err());
+
+        em.destroy(goblin);
+        assert_eq!(em.rename(goblin, "ghost"), Err(EsError::NoSuchEntity(goblin)));
+    }
+
+    #[test]
+    fn duplicate_names_follow_policy() {
+        let mut em = EntityManager::new();
+        let first = em.create_named("player");
+        assert_eq!(em.try_create_named("player"), Err(EsError::DuplicateName("player".to_string())));
+        let second = em.create();
+        assert_eq!(em.rename(second, "player"), Err(EsError::DuplicateName("player".to_string())));
+        assert_eq!(em.rename(first, "player"), Ok(()));
+
+        em.set_name_policy(NamePolicy::Overwrite);
+        em.rename(second, "player").unwrap();
+        assert_eq!(em.get_named("player"), Ok(second));
+        assert_eq!(em.name_of(first), None);
+    }
 }
 mod test_component_manager {
     extern crate entity_system;
-    use entity_system::{EntityManager, ComponentManager};
+    use entity_system::{EntityManager, ComponentManager, EsError};
 
     #[derive(Clone)]
     pub struct TestComponent {
@@ -175,7 +250,7 @@ mod test_component_manager {
             assert_eq!(component_entity_other.name, result.name);
         }
         {
-            let result = cm.get_mut::<TestComponent>(entity);
+            let mut result = cm.get_mut::<TestComponent>(entity);
             assert_eq!(component.name, result.name);
             result.name = "modified";
         }
@@ -234,4 +309,2035 @@ mod test_component_manager {
             assert!(e == entity || e == entity2);
         }
     }
+
+    #[test]
+    fn lookups_survive_storage_growth() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        let first = em.create();
+        cm.insert(first, TestComponent{name: "first"});
+        cm.insert(first, OtherComponent{name: "first_other"});
+
+        let mut entities = Vec::new();
+        for _ in 0..5000 {
+            let entity = em.create();
+            cm.insert(entity, TestComponent{name: "test"});
+            cm.insert(entity, OtherComponent{name: "other"});
+            entities.push(entity);
+        }
+        cm.insert(first, TestComponent{name: "first_again"});
+
+        {
+            let result = cm.find_for::<TestComponent>(first);
+            assert_eq!(result.len(), 2);
+            assert_eq!("first", result[0].name);
+            assert_eq!("first_again", result[1].name);
+            assert_eq!("first_other", cm.get::<OtherComponent>(first).name);
+        }
+        for &entity in entities.iter() {
+            let result = cm.find_for::<TestComponent>(entity);
+            assert_eq!(result.len(), 1);
+            assert_eq!("test", result[0].name);
+            assert_eq!("other", cm.find_for::<OtherComponent>(entity)[0].name);
+        }
+        {
+            cm.get_mut::<TestComponent>(first).name = "modified";
+            assert_eq!("modified", cm.find_for::<TestComponent>(first)[0].name);
+            assert_eq!(cm.find::<TestComponent>().len(), 5002);
+        }
+    }
+
+    #[test]
+    fn purges_components_of_entity() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        let entity = em.create();
+        let entity2 = em.create();
+
+        cm.insert(entity, TestComponent{name: "one"});
+        cm.insert(entity2, TestComponent{name: "two"});
+        cm.insert(entity, OtherComponent{name: "other"});
+        cm.insert(entity, TestComponent{name: "three"});
+        cm.insert(entity2, TestComponent{name: "four"});
+
+        em.destroy(entity);
+        assert!(cm.purge(entity));
+        assert!(!cm.purge(entity), "Purging an entity without components should return false");
+
+        let result = cm.find::<TestComponent>();
+        assert_eq!(result.len(), 2);
+        for meta in result.iter() {
+            assert_eq!(meta.entity, entity2);
+        }
+        assert_eq!(cm.find::<OtherComponent>().len(), 0);
+        assert_eq!(cm.find_entities_for_type::<TestComponent>(), vec![entity2]);
+
+        let result = cm.find_for::<TestComponent>(entity2);
+        assert_eq!(result.len(), 2);
+        assert_eq!("two", result[0].name);
+        assert_eq!("four", result[1].name);
+    }
+
+    #[test]
+    fn fallible_lookups_report_what_is_missing() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        let entity = em.create();
+        let entity2 = em.create();
+        let nobody = em.create();
+
+        assert!(cm.try_find::<TestComponent>().is_err());
+        cm.insert(entity, TestComponent{name: "one"});
+        cm.insert(entity2, OtherComponent{name: "other"});
+
+        match cm.try_get::<TestComponent>(entity2) {
+            Err(EsError::EntityLacksComponent(id, _)) => assert_eq!(id, entity2),
+            _ => panic!("expected EntityLacksComponent"),
+        }
+        assert_eq!(cm.try_get::<TestComponent>(nobody).err(), Some(EsError::NoSuchEntity(nobody)));
+        assert_eq!(cm.try_find_for::<TestComponent>(nobody).err(), Some(EsError::NoSuchEntity(nobody)));
+        assert!(cm.try_find_for_mut::<TestComponent>(entity2).is_err());
+        assert!(cm.try_get_mut::<TestComponent>(nobody).is_err());
+        match cm.try_find_mut::<u32>() {
+            Err(EsError::NoSuchComponentType(_)) => (),
+            _ => panic!("expected NoSuchComponentType"),
+        }
+
+        assert_eq!(cm.try_get::<TestComponent>(entity).unwrap().name, "one");
+        assert_eq!(cm.try_find::<TestComponent>().unwrap().len(), 1);
+        assert_eq!(cm.try_find_for::<OtherComponent>(entity2).unwrap()[0].name, "other");
+        cm.try_get_mut::<TestComponent>(entity).unwrap().name = "modified";
+        assert_eq!(cm.try_find_for_mut::<TestComponent>(entity).unwrap()[0].name, "modified");
+    }
+
+    #[derive(Clone)]
+    pub struct Poisoned {
+        pub damage: u32,
+    }
+
+    #[test]
+    fn removes_components_from_single_entity() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        let entity = em.create();
+        let entity2 = em.create();
+
+        cm.insert(entity, Poisoned{damage: 1});
+        cm.insert(entity2, Poisoned{damage: 2});
+        cm.insert(entity, Poisoned{damage: 3});
+        cm.insert(entity, TestComponent{name: "one"});
+
+        let removed = cm.remove_for::<Poisoned>(entity);
+        assert_eq!(removed.len(), 2);
+        assert_eq!(removed[0].damage, 1);
+        assert_eq!(removed[1].damage, 3);
+
+        assert!(cm.try_find_for::<Poisoned>(entity).is_err());
+        assert_eq!(cm.find_entities_for_type::<Poisoned>(), vec![entity2]);
+        assert_eq!(cm.get::<Poisoned>(entity2).damage, 2);
+        assert_eq!(cm.get::<TestComponent>(entity).name, "one");
+        assert!(cm.remove_for::<Poisoned>(entity).is_empty());
+        assert!(cm.remove_for::<OtherComponent>(entity).is_empty());
+    }
+
+    #[test]
+    fn removes_one_component_from_entity() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        let entity = em.create();
+        let entity2 = em.create();
+
+        cm.insert(entity, Poisoned{damage: 1});
+        cm.insert(entity, Poisoned{damage: 2});
+        cm.insert(entity2, Poisoned{damage: 4});
+        cm.insert(entity, Poisoned{damage: 3});
+
+        assert_eq!(cm.remove_one::<Poisoned>(entity, 1).unwrap().damage, 2);
+        assert!(cm.remove_one::<Poisoned>(entity, 2).is_none());
+        assert!(cm.remove_one::<OtherComponent>(entity, 0).is_none());
+
+        let result = cm.find_for::<Poisoned>(entity);
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].damage, 1);
+        assert_eq!(result[1].damage, 3);
+        assert_eq!(cm.find::<Poisoned>().len(), 3);
+
+        assert_eq!(cm.remove_one::<Poisoned>(entity, 0).unwrap().damage, 1);
+        assert_eq!(cm.remove_one::<Poisoned>(entity, 0).unwrap().damage, 3);
+        assert!(cm.try_find_for::<Poisoned>(entity).is_err());
+        assert_eq!(cm.get::<Poisoned>(entity2).damage, 4);
+    }
+
+    pub struct NotClone {
+        pub value: u32,
+    }
+
+    #[test]
+    fn iterates_without_cloning() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        let entity = em.create();
+        let entity2 = em.create();
+
+        cm.insert(entity, NotClone{value: 1});
+        cm.insert(entity2, NotClone{value: 2});
+        cm.insert(entity, NotClone{value: 3});
+
+        {
+            let result: Vec<_> = cm.iter::<NotClone>()
+                .map(|meta| (meta.entity, meta.component.value))
+                .collect();
+            assert_eq!(result, vec![(entity, 1), (entity2, 2), (entity, 3)]);
+        }
+        for mut meta in cm.iter_mut::<NotClone>() {
+            meta.component.value *= 10;
+        }
+        {
+            let result: Vec<_> = cm.iter_for::<NotClone>(entity).map(|c| c.value).collect();
+            assert_eq!(result, vec![10, 30]);
+        }
+        for mut component in cm.iter_for_mut::<NotClone>(entity2) {
+            component.value += 1;
+        }
+        assert_eq!(cm.iter_for::<NotClone>(entity2).next().unwrap().value, 21);
+
+        assert_eq!(cm.iter::<TestComponent>().count(), 0);
+        assert_eq!(cm.iter_mut::<TestComponent>().count(), 0);
+        assert_eq!(cm.iter_for::<TestComponent>(entity).count(), 0);
+        assert_eq!(cm.iter_for_mut::<NotClone>(em.create()).count(), 0);
+    }
+}
+
+mod test_world {
+    extern crate entity_system;
+    use entity_system::{EntityManager, ComponentManager, EventBus, Processor, Resources, World};
+
+    #[derive(Clone)]
+    pub struct Counter {
+        pub physics: u32,
+        pub rendering: u32,
+    }
+
+    #[derive(Clone)]
+    pub struct Position {
+        pub x: i32,
+    }
+
+    struct Physics;
+
+    impl Processor for Physics {
+        fn process(&mut self, em: &mut EntityManager, cm: &mut ComponentManager,
+                   _: &mut Resources, _: &mut EventBus) {
+            let counter = em.get_named("counter").unwrap();
+            cm.get_mut::<Counter>(counter).physics += 1;
+            for mut meta in cm.find_mut::<Position>() {
+                meta.component.x += 1;
+            }
+        }
+    }
+
+    struct Rendering;
+
+    impl Processor for Rendering {
+        fn process(&mut self, em: &mut EntityManager, cm: &mut ComponentManager,
+                   _: &mut Resources, _: &mut EventBus) {
+            let counter = em.get_named("counter").unwrap();
+            cm.get_mut::<Counter>(counter).rendering += 1;
+        }
+    }
+
+    #[test]
+    fn runs_processors_on_their_interval() {
+        let mut world = World::new();
+        let counter = world.entities_mut().create_named("counter");
+        world.components_mut().insert(counter, Counter{physics: 0, rendering: 0});
+        let entity = world.entities_mut().create();
+        world.components_mut().insert(entity, Position{x: 0});
+
+        world.add_processor(Physics, 10);
+        world.add_processor(Rendering, 1);
+
+        for _ in 0..25 {
+            world.tick();
+        }
+
+        assert_eq!(world.current_tick(), 25);
+        let result = world.components().get::<Counter>(counter);
+        assert_eq!(result.physics, 3);
+        assert_eq!(result.rendering, 25);
+        assert_eq!(world.components().get::<Position>(entity).x, 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_interval_is_rejected() {
+        let mut world = World::new();
+        world.add_processor(Rendering, 0);
+    }
+}
+mod test_query {
+    extern crate entity_system;
+    use entity_system::{EntityManager, ComponentManager, With, Without};
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Position {
+        pub x: i32,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Velocity {
+        pub x: i32,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Frozen;
+
+    fn setup() -> (ComponentManager, Vec<entity_system::EntityId>) {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        let entities: Vec<_> = (0..4).map(|_| em.create()).collect();
+
+        cm.insert(entities[0], Position{x: 1});
+        cm.insert(entities[0], Velocity{x: 10});
+        cm.insert(entities[1], Position{x: 2});
+        cm.insert(entities[2], Position{x: 3});
+        cm.insert(entities[2], Velocity{x: 30});
+        cm.insert(entities[2], Frozen);
+        cm.insert(entities[3], Velocity{x: 40});
+        (cm, entities)
+    }
+
+    #[test]
+    fn joins_entities_with_all_types() {
+        let (mut cm, entities) = setup();
+
+        let mut visited = Vec::new();
+        for (entity, position, mut velocity) in cm.query::<(&Position, &mut Velocity)>() {
+            velocity.x += position.x;
+            visited.push(entity);
+        }
+        visited.sort();
+        assert_eq!(visited, vec![entities[0], entities[2]]);
+
+        assert_eq!(cm.get::<Velocity>(entities[0]).x, 11);
+        assert_eq!(cm.get::<Velocity>(entities[2]).x, 33);
+        assert_eq!(cm.get::<Velocity>(entities[3]).x, 40);
+    }
+
+    #[test]
+    fn optional_components_do_not_restrict() {
+        let (mut cm, entities) = setup();
+
+        let mut result: Vec<_> = cm.query::<(&Position, Option<&Velocity>)>()
+            .map(|(entity, position, velocity)| (entity, position.x, velocity.map(|v| v.x)))
+            .collect();
+        result.sort();
+        assert_eq!(result, vec![(entities[0], 1, Some(10)),
+                                (entities[1], 2, None),
+                                (entities[2], 3, Some(30))]);
+
+        for (_, mut position, velocity) in cm.query::<(&mut Position, Option<&mut Velocity>)>() {
+            if let Some(velocity) = velocity {
+                position.x = velocity.x;
+            }
+        }
+        assert_eq!(cm.get::<Position>(entities[0]).x, 10);
+        assert_eq!(cm.get::<Position>(entities[1]).x, 2);
+    }
+
+    #[test]
+    fn filters_restrict_entities() {
+        let (mut cm, entities) = setup();
+
+        let result: Vec<_> = cm.query_filtered::<(&Position, &Velocity), Without<Frozen>>()
+            .map(|(entity, _, _)| entity)
+            .collect();
+        assert_eq!(result, vec![entities[0]]);
+
+        let result: Vec<_> = cm.query_filtered::<(&Position,), (With<Frozen>, With<Velocity>)>()
+            .map(|(entity, _)| entity)
+            .collect();
+        assert_eq!(result, vec![entities[2]]);
+    }
+
+    #[test]
+    fn missing_types_yield_nothing() {
+        let (mut cm, _) = setup();
+        assert_eq!(cm.query::<(&Position, &String)>().count(), 0);
+        assert_eq!(cm.query_filtered::<(&Position,), Without<String>>().count(), 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn aliasing_mutable_access_is_rejected() {
+        let (mut cm, _) = setup();
+        cm.query::<(&mut Position, &Position)>().count();
+    }
+}
+mod test_archetypes {
+    extern crate entity_system;
+    use entity_system::{EntityManager, ComponentManager, Without};
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Position {
+        pub x: i32,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Velocity {
+        pub x: i32,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Frozen;
+
+    #[test]
+    fn entities_move_between_archetypes() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::with_archetypes();
+        let first = em.create();
+        let second = em.create();
+
+        cm.insert(first, Position{x: 1});
+        cm.insert(second, Position{x: 2});
+        cm.insert(first, Velocity{x: 10});
+        cm.insert(first, Velocity{x: 20});
+        assert_eq!(cm.get::<Position>(first).x, 1);
+        assert_eq!(cm.get::<Position>(second).x, 2);
+        assert_eq!(cm.find_for::<Velocity>(first), vec![Velocity{x: 10}, Velocity{x: 20}]);
+
+        assert_eq!(cm.remove_one::<Velocity>(first, 0), Some(Velocity{x: 10}));
+        assert_eq!(cm.remove_for::<Velocity>(first), vec![Velocity{x: 20}]);
+        assert_eq!(cm.get::<Position>(first).x, 1);
+        assert!(cm.try_get::<Velocity>(first).is_err());
+
+        let mut entities = cm.find_entities_for_type::<Position>();
+        entities.sort();
+        assert_eq!(entities, vec![first, second]);
+
+        assert!(cm.purge(first));
+        assert!(!cm.purge(first));
+        assert_eq!(cm.find_entities_for_type::<Position>(), vec![second]);
+    }
+
+    #[test]
+    fn removing_a_type_keeps_other_components() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::with_archetypes();
+        let entity = em.create();
+
+        cm.insert(entity, Position{x: 1});
+        cm.insert(entity, Velocity{x: 10});
+        assert!(cm.remove::<Velocity>());
+        assert!(!cm.contains::<Velocity>());
+        assert_eq!(cm.get::<Position>(entity).x, 1);
+
+        cm.insert(entity, Velocity{x: 20});
+        assert_eq!(cm.get::<Velocity>(entity).x, 20);
+        assert_eq!(cm.iter::<Velocity>().count(), 1);
+    }
+
+    #[test]
+    fn queries_walk_archetype_tables() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::with_archetypes();
+        let entities: Vec<_> = (0..4).map(|_| em.create()).collect();
+
+        cm.insert(entities[0], Position{x: 1});
+        cm.insert(entities[0], Velocity{x: 10});
+        cm.insert(entities[1], Position{x: 2});
+        cm.insert(entities[2], Position{x: 3});
+        cm.insert(entities[2], Velocity{x: 30});
+        cm.insert(entities[2], Frozen);
+        cm.insert(entities[3], Velocity{x: 40});
+
+        let mut visited = Vec::new();
+        for (entity, position, mut velocity) in cm.query::<(&Position, &mut Velocity)>() {
+            velocity.x += position.x;
+            visited.push(entity);
+        }
+        visited.sort();
+        assert_eq!(visited, vec![entities[0], entities[2]]);
+        assert_eq!(cm.get::<Velocity>(entities[2]).x, 33);
+
+        let mut result: Vec<_> = cm.query::<(&Position, Option<&Velocity>)>()
+            .map(|(entity, position, velocity)| (entity, position.x, velocity.map(|v| v.x)))
+            .collect();
+        result.sort();
+        assert_eq!(result, vec![(entities[0], 1, Some(11)),
+                                (entities[1], 2, None),
+                                (entities[2], 3, Some(33))]);
+
+        let mut result: Vec<_> = cm.query_filtered::<(&Velocity,), Without<Frozen>>()
+            .map(|(entity, _)| entity)
+            .collect();
+        result.sort();
+        assert_eq!(result, vec![entities[0], entities[3]]);
+    }
+}
+mod test_storage_kinds {
+    extern crate entity_system;
+    use entity_system::{EntityManager, ComponentManager, StorageKind, With, Without};
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Position {
+        pub x: i32,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Stunned {
+        pub turns: u32,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Frozen;
+
+    fn setup(mut cm: ComponentManager) -> (ComponentManager, Vec<entity_system::EntityId>) {
+        let mut em = EntityManager::new();
+        cm.register::<Stunned>(StorageKind::SparseSet);
+        cm.register::<Frozen>(StorageKind::HashMap);
+        let entities: Vec<_> = (0..4).map(|_| em.create()).collect();
+
+        cm.insert(entities[0], Position{x: 1});
+        cm.insert(entities[0], Stunned{turns: 2});
+        cm.insert(entities[1], Position{x: 2});
+        cm.insert(entities[1], Frozen);
+        cm.insert(entities[2], Position{x: 3});
+        cm.insert(entities[2], Stunned{turns: 1});
+        cm.insert(entities[2], Frozen);
+        cm.insert(entities[3], Stunned{turns: 5});
+        (cm, entities)
+    }
+
+    fn check_kinds(cm: ComponentManager) {
+        let (mut cm, entities) = setup(cm);
+        assert_eq!(cm.storage_kind::<Stunned>(), StorageKind::SparseSet);
+        assert_eq!(cm.storage_kind::<Position>(), StorageKind::Dense);
+
+        assert_eq!(cm.get::<Stunned>(entities[3]).turns, 5);
+        assert_eq!(cm.remove_for::<Stunned>(entities[0]), vec![Stunned{turns: 2}]);
+        assert_eq!(cm.get::<Position>(entities[0]).x, 1);
+        assert_eq!(cm.get::<Stunned>(entities[2]).turns, 1);
+        assert!(cm.try_get::<Stunned>(entities[0]).is_err());
+
+        let mut result: Vec<_> = cm.query::<(&Position, Option<&Stunned>)>()
+            .map(|(entity, position, stunned)| (entity, position.x, stunned.map(|s| s.turns)))
+            .collect();
+        result.sort();
+        assert_eq!(result, vec![(entities[0], 1, None),
+                                (entities[1], 2, None),
+                                (entities[2], 3, Some(1))]);
+
+        let mut result: Vec<_> = cm.query::<(&mut Stunned, Option<&Position>)>()
+            .map(|(entity, mut stunned, position)| {
+                stunned.turns -= 1;
+                (entity, position.map(|p| p.x))
+            })
+            .collect();
+        result.sort();
+        assert_eq!(result, vec![(entities[2], Some(3)), (entities[3], None)]);
+        assert_eq!(cm.get::<Stunned>(entities[3]).turns, 4);
+
+        let result: Vec<_> = cm.query_filtered::<(&Position,), (With<Frozen>, Without<Stunned>)>()
+            .map(|(entity, _)| entity)
+            .collect();
+        assert_eq!(result, vec![entities[1]]);
+
+        assert!(cm.purge(entities[2]));
+        assert!(cm.try_get::<Stunned>(entities[2]).is_err());
+        assert!(cm.try_get::<Frozen>(entities[2]).is_err());
+        assert_eq!(cm.find_entities_for_type::<Frozen>(), vec![entities[1]]);
+    }
+
+    #[test]
+    fn kinds_mix_in_per_type_tables() {
+        check_kinds(ComponentManager::new());
+    }
+
+    #[test]
+    fn kinds_mix_with_archetypes() {
+        check_kinds(ComponentManager::with_archetypes());
+    }
+
+    #[test]
+    fn sparse_set_ignores_stale_ids() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        cm.register::<Stunned>(StorageKind::SparseSet);
+
+        let stale = em.create();
+        cm.insert(stale, Stunned{turns: 1});
+        em.destroy(stale);
+        let fresh = em.create();
+        assert_eq!(stale.index(), fresh.index());
+        assert!(cm.try_get::<Stunned>(fresh).is_err());
+        assert_eq!(cm.get::<Stunned>(stale).turns, 1);
+    }
+
+    #[test]
+    fn sparse_set_keeps_stale_rows_when_slot_is_reused() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        cm.register::<Stunned>(StorageKind::SparseSet);
+
+        let stale = em.create();
+        cm.insert(stale, Stunned{turns: 1});
+        em.destroy(stale);
+        let fresh = em.create();
+        cm.insert(fresh, Stunned{turns: 2});
+        assert_eq!(cm.get::<Stunned>(stale).turns, 1);
+        assert_eq!(cm.get::<Stunned>(fresh).turns, 2);
+
+        assert!(cm.purge(stale));
+        assert!(!cm.has::<Stunned>(stale));
+        assert_eq!(cm.get::<Stunned>(fresh).turns, 2);
+        let turns: Vec<u32> = cm.iter::<Stunned>().map(|meta| meta.component.turns).collect();
+        assert_eq!(turns, vec![2]);
+
+        cm.insert(stale, Stunned{turns: 3});
+        assert!(cm.purge(fresh));
+        assert_eq!(cm.get::<Stunned>(stale).turns, 3);
+        assert_eq!(cm.iter::<Stunned>().count(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn kind_must_be_chosen_before_insert() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        cm.insert(em.create(), Stunned{turns: 1});
+        cm.register::<Stunned>(StorageKind::SparseSet);
+    }
+
+    fn check_register_after_remove(mut cm: ComponentManager) {
+        let mut em = EntityManager::new();
+        let (a, b) = (em.create(), em.create());
+        cm.insert(a, Stunned{turns: 1});
+        cm.insert(a, Position{x: 1});
+        cm.insert(b, Position{x: 2});
+        cm.insert(b, Stunned{turns: 2});
+        cm.insert(b, Frozen);
+
+        assert!(cm.remove::<Stunned>());
+        cm.register::<Stunned>(StorageKind::SparseSet);
+        assert_eq!(cm.storage_kind::<Stunned>(), StorageKind::SparseSet);
+        assert!(!cm.contains::<Stunned>());
+
+        cm.insert(b, Stunned{turns: 3});
+        assert_eq!(cm.get::<Stunned>(b).turns, 3);
+        assert!(!cm.has::<Stunned>(a));
+        assert_eq!(cm.get::<Position>(a).x, 1);
+        assert_eq!(cm.get::<Position>(b).x, 2);
+        assert!(cm.has::<Frozen>(b));
+
+        let mut result: Vec<_> = cm.query::<(&Position, Option<&Stunned>)>()
+            .map(|(entity, position, stunned)| (entity, position.x, stunned.map(|s| s.turns)))
+            .collect();
+        result.sort();
+        assert_eq!(result, vec![(a, 1, None), (b, 2, Some(3))]);
+    }
+
+    #[test]
+    fn kind_can_be_chosen_again_after_remove() {
+        check_register_after_remove(ComponentManager::new());
+    }
+
+    #[test]
+    fn kind_can_be_chosen_again_after_remove_with_archetypes() {
+        check_register_after_remove(ComponentManager::with_archetypes());
+    }
+}
+mod test_registry {
+    extern crate entity_system;
+    extern crate serde;
+    use entity_system::{EntityManager, ComponentManager, EsError, Format, Registry};
+    use self::serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Position {
+        pub x: i32,
+        pub y: i32,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Poisoned {
+        pub damage: u32,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Transient;
+
+    fn registry() -> Registry {
+        let mut registry = Registry::new();
+        registry.register::<Position>("Position");
+        registry.register::<Poisoned>("Poisoned");
+        registry
+    }
+
+    fn round_trip(format: Format) {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        let player = em.create_named("player");
+        let destroyed = em.create();
+        let goblin = em.create();
+        em.destroy(destroyed);
+
+        cm.insert(player, Position{x: 1, y: 2});
+        cm.insert(player, Transient);
+        cm.insert(goblin, Position{x: 3, y: 4});
+        cm.insert(goblin, Poisoned{damage: 1});
+        cm.insert(goblin, Poisoned{damage: 2});
+
+        let registry = registry();
+        let mut buffer = Vec::new();
+        registry.save(&em, &cm, format, &mut buffer).unwrap();
+        let (mut loaded_em, loaded_cm) = registry.load(format, &buffer[..]).unwrap();
+
+        assert_eq!(loaded_em.get_named("player"), Ok(player));
+        assert_eq!(loaded_em.name_of(player), Some("player"));
+        assert!(loaded_em.is_alive(goblin));
+        assert!(!loaded_em.is_alive(destroyed));
+        assert_eq!(loaded_em.create(), em.create());
+
+        assert_eq!(loaded_cm.get::<Position>(player), Position{x: 1, y: 2});
+        assert_eq!(loaded_cm.get::<Position>(goblin), Position{x: 3, y: 4});
+        assert_eq!(loaded_cm.find_for::<Poisoned>(goblin),
+                   vec![Poisoned{damage: 1}, Poisoned{damage: 2}]);
+        assert!(!loaded_cm.contains::<Transient>());
+    }
+
+    #[test]
+    fn round_trips_json() {
+        round_trip(Format::Json);
+    }
+
+    #[test]
+    fn round_trips_binary() {
+        round_trip(Format::Binary);
+    }
+
+    #[test]
+    fn unregistered_names_are_an_error() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        cm.insert(em.create(), Position{x: 1, y: 2});
+
+        let mut buffer = Vec::new();
+        registry().save(&em, &cm, Format::Json, &mut buffer).unwrap();
+        let result = Registry::new().load(Format::Json, &buffer[..]);
+        assert_eq!(result.err(), Some(EsError::UnregisteredComponentType("Position".to_string())));
+    }
+}
+mod test_hierarchy {
+    extern crate entity_system;
+    use entity_system::{EntityManager, ComponentManager, EsError, World};
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Position {
+        pub x: i32,
+    }
+
+    #[test]
+    fn tracks_parents_and_children() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        let player = em.create();
+        let bag = em.create();
+        let sword = em.create();
+        let potion = em.create();
+
+        cm.set_parent(bag, player).unwrap();
+        cm.set_parent(sword, player).unwrap();
+        cm.set_parent(potion, bag).unwrap();
+        assert_eq!(cm.children_of(player), &[bag, sword]);
+        assert_eq!(cm.parent_of(potion), Some(bag));
+        assert_eq!(cm.ancestors(potion).collect::<Vec<_>>(), vec![bag, player]);
+        assert_eq!(cm.descendants(player).collect::<Vec<_>>(), vec![bag, potion, sword]);
+
+        assert_eq!(cm.set_parent(player, potion), Err(EsError::HierarchyCycle(player, potion)));
+        assert_eq!(cm.set_parent(player, player), Err(EsError::HierarchyCycle(player, player)));
+
+        cm.set_parent(potion, player).unwrap();
+        assert_eq!(cm.children_of(bag), &[] as &[entity_system::EntityId]);
+        assert_eq!(cm.children_of(player), &[bag, sword, potion]);
+
+        assert_eq!(cm.remove_parent(sword), Some(player));
+        assert_eq!(cm.parent_of(sword), None);
+        assert_eq!(cm.children_of(player), &[bag, potion]);
+    }
+
+    #[test]
+    fn purging_keeps_hierarchy_consistent() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        let parent = em.create();
+        let child = em.create();
+        let grandchild = em.create();
+        cm.set_parent(child, parent).unwrap();
+        cm.set_parent(grandchild, child).unwrap();
+
+        em.destroy(child);
+        cm.purge(child);
+        assert_eq!(cm.children_of(parent), &[] as &[entity_system::EntityId]);
+        assert_eq!(cm.parent_of(grandchild), None);
+    }
+
+    #[test]
+    fn destroys_subtrees() {
+        let mut world = World::new();
+        let (root, child, grandchild, sibling) = {
+            let em = world.entities_mut();
+            (em.create(), em.create(), em.create(), em.create())
+        };
+        {
+            let cm = world.components_mut();
+            cm.insert(grandchild, Position{x: 1});
+            cm.insert(sibling, Position{x: 2});
+            cm.set_parent(child, root).unwrap();
+            cm.set_parent(grandchild, child).unwrap();
+            cm.set_parent(sibling, root).unwrap();
+        }
+
+        assert_eq!(world.destroy_subtree(child), vec![child, grandchild]);
+        assert!(!world.entities().is_alive(grandchild));
+        assert!(world.entities().is_alive(sibling));
+        assert!(world.components().try_get::<Position>(grandchild).is_err());
+        assert_eq!(world.components().children_of(root), &[sibling]);
+    }
+}
+mod test_change_detection {
+    extern crate entity_system;
+    use entity_system::{EntityManager, ComponentManager};
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Position {
+        pub x: i32,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Velocity {
+        pub x: i32,
+    }
+
+    fn check_tracking(mut cm: ComponentManager) {
+        let mut em = EntityManager::new();
+        cm.track_removals::<Position>();
+        cm.track_removals::<Velocity>();
+        let moving = em.create();
+        let still = em.create();
+        cm.insert(moving, Position{x: 0});
+        cm.insert(moving, Velocity{x: 1});
+        cm.insert(still, Position{x: 5});
+
+        let mut added = cm.added_since::<Position>(0);
+        added.sort();
+        assert_eq!(added, vec![moving, still]);
+
+        let since = cm.increment_change_tick();
+        assert!(cm.added_since::<Position>(since).is_empty());
+        assert!(cm.changed_since::<Position>(since).is_empty());
+
+        for (_, mut position, velocity) in cm.query::<(&mut Position, &Velocity)>() {
+            position.x += velocity.x;
+        }
+        for (_, position) in cm.query::<(&mut Position,)>() {
+            assert!(position.x >= 0);
+        }
+        assert_eq!(cm.get::<Position>(still).x, 5);
+        assert_eq!(cm.changed_since::<Position>(since), vec![moving]);
+
+        let since = cm.increment_change_tick();
+        assert_eq!(cm.get_mut::<Position>(still).x, 5);
+        assert!(cm.changed_since::<Position>(since).is_empty());
+        cm.get_mut::<Position>(still).x = 6;
+        assert_eq!(cm.changed_since::<Position>(since), vec![still]);
+
+        let since = cm.increment_change_tick();
+        for mut meta in cm.iter_mut::<Position>() {
+            if meta.entity == moving {
+                meta.component.x += 1;
+            }
+        }
+        assert_eq!(cm.find_for_mut::<Position>(still)[0].x, 6);
+        for mut position in cm.iter_for_mut::<Position>(still) {
+            if position.x > 10 {
+                position.x = 10;
+            }
+        }
+        assert_eq!(cm.changed_since::<Position>(since), vec![moving]);
+
+        let since = cm.increment_change_tick();
+        cm.remove_for::<Velocity>(moving);
+        em.destroy(still);
+        cm.purge(still);
+        assert_eq!(cm.removed_since::<Velocity>(since), vec![moving]);
+        assert_eq!(cm.removed_since::<Position>(since), vec![still]);
+        assert_eq!(cm.get::<Position>(moving).x, 2);
+        assert!(cm.changed_since::<Position>(since).is_empty());
+
+        cm.forget_removed(cm.change_tick());
+        assert!(cm.removed_since::<Velocity>(0).is_empty());
+    }
+
+    #[test]
+    fn untracked_removals_are_not_logged() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        let a = em.create();
+        cm.insert(a, Position{x: 0});
+        cm.insert(a, Velocity{x: 1});
+        cm.track_removals::<Velocity>();
+
+        cm.remove_for::<Position>(a);
+        cm.remove_for::<Velocity>(a);
+        assert!(cm.removed_since::<Position>(0).is_empty());
+        assert_eq!(cm.removed_since::<Velocity>(0), vec![a]);
+    }
+
+    #[test]
+    fn tracks_changes_in_per_type_tables() {
+        check_tracking(ComponentManager::new());
+    }
+
+    #[test]
+    fn tracks_changes_with_archetypes() {
+        check_tracking(ComponentManager::with_archetypes());
+    }
+}
+mod test_hooks {
+    extern crate entity_system;
+    use std::any::TypeId;
+    use std::sync::{Arc, Mutex};
+    use entity_system::{EntityManager, ComponentManager, ComponentEvent, ComponentEventKind, EntityId};
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Collider {
+        pub radius: u32,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Health {
+        pub hp: u32,
+    }
+
+    fn check_hooks(mut cm: ComponentManager) {
+        let mut em = EntityManager::new();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let inserted = log.clone();
+        cm.on_insert::<Collider, _>(move |id, collider| inserted.lock().unwrap().push(("insert", id, collider.radius)));
+        let removed = log.clone();
+        cm.on_remove::<Collider, _>(move |id, collider| removed.lock().unwrap().push(("remove", id, collider.radius)));
+        cm.queue_events::<Collider>();
+
+        let ball = em.create();
+        let wall = em.create();
+        cm.insert(ball, Health{hp: 3});
+        cm.insert(ball, Collider{radius: 1});
+        cm.insert(ball, Collider{radius: 2});
+        cm.insert(wall, Collider{radius: 9});
+        cm.remove_one::<Collider>(ball, 0);
+        cm.remove_for::<Collider>(ball);
+        em.destroy(wall);
+        cm.purge(wall);
+        cm.remove::<Health>();
+
+        assert_eq!(*log.lock().unwrap(), vec![
+            ("insert", ball, 1),
+            ("insert", ball, 2),
+            ("insert", wall, 9),
+            ("remove", ball, 1),
+            ("remove", ball, 2),
+            ("remove", wall, 9),
+        ]);
+
+        let collider = TypeId::of::<Collider>();
+        let events: Vec<ComponentEvent> = cm.drain_events().collect();
+        let kinds: Vec<(EntityId, ComponentEventKind)> = events.iter().map(|event| (event.entity, event.kind)).collect();
+        assert!(events.iter().all(|event| event.type_id == collider));
+        assert_eq!(kinds, vec![
+            (ball, ComponentEventKind::Inserted),
+            (ball, ComponentEventKind::Inserted),
+            (wall, ComponentEventKind::Inserted),
+            (ball, ComponentEventKind::Removed),
+            (ball, ComponentEventKind::Removed),
+            (wall, ComponentEventKind::Removed),
+        ]);
+        assert_eq!(cm.drain_events().count(), 0);
+    }
+
+    #[test]
+    fn hooks_fire_in_per_type_tables() {
+        check_hooks(ComponentManager::new());
+    }
+
+    #[test]
+    fn hooks_fire_with_archetypes() {
+        check_hooks(ComponentManager::with_archetypes());
+    }
+}
+mod test_events {
+    extern crate entity_system;
+    use entity_system::{EntityManager, ComponentManager, EntityId, EventBus, EventReader, Processor, Resources, World};
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Damage {
+        pub entity: EntityId,
+        pub amount: u32,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Health {
+        pub hp: u32,
+    }
+
+    #[test]
+    fn readers_see_every_event() {
+        let mut bus = EventBus::new();
+        let mut first = bus.reader::<u32>();
+        bus.send(1u32);
+        let mut second = bus.reader::<u32>();
+        bus.send(2u32);
+        bus.send("ignored");
+
+        assert_eq!(bus.read(&mut first).cloned().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(bus.read(&mut first).count(), 0);
+        bus.update();
+        assert_eq!(bus.unread(&second), 1);
+        assert_eq!(bus.read(&mut second).cloned().collect::<Vec<_>>(), vec![2]);
+
+        bus.send(3u32);
+        bus.remove_reader(second);
+        bus.update();
+        assert_eq!(bus.read(&mut first).cloned().collect::<Vec<_>>(), vec![3]);
+    }
+
+    #[test]
+    fn events_expire() {
+        let mut bus = EventBus::new();
+        bus.set_max_age(Some(2));
+        let mut reader = bus.reader::<u32>();
+        bus.send(1u32);
+        bus.update();
+        bus.send(2u32);
+        bus.update();
+        assert_eq!(bus.unread(&reader), 1);
+        assert_eq!(bus.read(&mut reader).cloned().collect::<Vec<_>>(), vec![2]);
+    }
+
+    struct Combat;
+
+    impl Processor for Combat {
+        fn process(&mut self, em: &mut EntityManager, _: &mut ComponentManager,
+                   _: &mut Resources, events: &mut EventBus) {
+            let target = em.get_named("target").unwrap();
+            events.send(Damage{entity: target, amount: 2});
+        }
+    }
+
+    struct Wounds {
+        reader: EventReader<Damage>,
+    }
+
+    impl Processor for Wounds {
+        fn process(&mut self, _: &mut EntityManager, cm: &mut ComponentManager,
+                   _: &mut Resources, events: &mut EventBus) {
+            for event in events.read(&mut self.reader) {
+                cm.get_mut::<Health>(event.entity).hp -= event.amount;
+            }
+        }
+    }
+
+    #[test]
+    fn processors_talk_through_events() {
+        let mut world = World::new();
+        let target = world.entities_mut().create_named("target");
+        world.components_mut().insert(target, Health{hp: 10});
+        let reader = world.events_mut().reader::<Damage>();
+        world.add_processor(Combat, 1);
+        world.add_processor(Wounds{reader}, 1);
+
+        for _ in 0..3 {
+            world.tick();
+        }
+        assert_eq!(world.components().get::<Health>(target).hp, 4);
+    }
+}
+mod test_resources {
+    extern crate entity_system;
+    use entity_system::{EntityManager, ComponentManager, EventBus, EsError, Processor, Resources, World};
+
+    #[derive(Debug, PartialEq)]
+    pub struct DeltaTime(pub u32);
+
+    #[derive(Debug, PartialEq)]
+    pub struct Clock(pub u32);
+
+    #[derive(Clone)]
+    pub struct Position {
+        pub x: u32,
+    }
+
+    struct Movement;
+
+    impl Processor for Movement {
+        fn process(&mut self, _: &mut EntityManager, cm: &mut ComponentManager,
+                   resources: &mut Resources, _: &mut EventBus) {
+            let dt = resources.resource::<DeltaTime>().0;
+            for mut meta in cm.iter_mut::<Position>() {
+                resources.resource_mut::<Clock>().0 += dt;
+                meta.component.x += dt;
+            }
+        }
+    }
+
+    #[test]
+    fn processors_share_resources() {
+        let mut world = World::new();
+        world.insert_resource(DeltaTime(2));
+        world.insert_resource(Clock(0));
+        let entity = world.entities_mut().create();
+        world.components_mut().insert(entity, Position{x: 1});
+        world.add_processor(Movement, 1);
+
+        world.tick();
+        world.tick();
+        assert_eq!(world.resource::<Clock>(), &Clock(4));
+        assert_eq!(world.components().get::<Position>(entity).x, 5);
+        assert_eq!(world.insert_resource(DeltaTime(3)), Some(DeltaTime(2)));
+    }
+
+    #[test]
+    fn missing_resources_are_an_error() {
+        let mut resources = Resources::new();
+        assert!(!resources.has_resource::<Clock>());
+        assert_eq!(resources.try_resource::<Clock>(), Err(EsError::NoSuchResource(std::any::type_name::<Clock>())));
+        resources.insert_resource(Clock(1));
+        resources.try_resource_mut::<Clock>().unwrap().0 += 1;
+        assert_eq!(resources.remove_resource::<Clock>(), Some(Clock(2)));
+        assert!(resources.try_resource_mut::<Clock>().is_err());
+    }
+}
+mod test_parallel {
+    extern crate entity_system;
+    use std::cell::{Cell, RefCell};
+    use std::panic;
+    use std::rc::Rc;
+    use entity_system::{Access, EntityManager, ComponentManager, EventBus, ParallelProcessor, Processor, Resources, View, World};
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Position {
+        pub x: i32,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Velocity {
+        pub x: i32,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Trail {
+        pub xs: Vec<i32>,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Health {
+        pub hp: i32,
+    }
+
+    struct Movement;
+
+    impl ParallelProcessor for Movement {
+        fn access(&self) -> Access {
+            Access::new().read::<Velocity>().write::<Position>()
+        }
+
+        fn process(&mut self, view: &mut View) {
+            for mut meta in view.writes.iter_mut::<Position>() {
+                meta.component.x += view.reads.get::<Velocity>(meta.entity).map_or(0, |velocity| velocity.x);
+            }
+        }
+    }
+
+    struct Regeneration;
+
+    impl ParallelProcessor for Regeneration {
+        fn access(&self) -> Access {
+            Access::new().write::<Health>()
+        }
+
+        fn process(&mut self, view: &mut View) {
+            let entity = view.reads.entities().get_named("player").unwrap();
+            view.writes.get_mut::<Health>(entity).unwrap().hp += 1;
+        }
+    }
+
+    struct Tracking;
+
+    impl ParallelProcessor for Tracking {
+        fn access(&self) -> Access {
+            Access::new().read::<Position>().write::<Trail>()
+        }
+
+        fn process(&mut self, view: &mut View) {
+            let reads = view.reads;
+            for mut meta in view.writes.iter_mut::<Trail>() {
+                let x = reads.get::<Position>(meta.entity).unwrap().x;
+                meta.component.xs.push(x);
+            }
+        }
+    }
+
+    struct Faulty;
+
+    impl ParallelProcessor for Faulty {
+        fn access(&self) -> Access {
+            Access::new().write::<Position>()
+        }
+
+        fn process(&mut self, _: &mut View) {
+            panic!("processor failed");
+        }
+    }
+
+    struct Logging;
+
+    impl Processor for Logging {
+        fn process(&mut self, _: &mut EntityManager, cm: &mut ComponentManager,
+                   resources: &mut Resources, _: &mut EventBus) {
+            for (_, log, position) in cm.query::<(&Rc<RefCell<Vec<i32>>>, &Position)>() {
+                log.borrow_mut().push(position.x);
+            }
+            let ticks = resources.resource::<Rc<Cell<i32>>>();
+            ticks.set(ticks.get() + 1);
+        }
+    }
+
+    struct Knockback;
+
+    impl Processor for Knockback {
+        fn process(&mut self, em: &mut EntityManager, cm: &mut ComponentManager,
+                   _: &mut Resources, _: &mut EventBus) {
+            let entity = em.get_named("player").unwrap();
+            cm.get_mut::<Velocity>(entity).x *= -1;
+        }
+    }
+
+    #[test]
+    fn only_parallel_processors_need_send_components() {
+        let mut world = World::new();
+        let player = world.entities_mut().create_named("player");
+        let log = Rc::new(RefCell::new(Vec::<i32>::new()));
+        world.components_mut().insert(player, log.clone());
+        world.components_mut().insert(player, Position{x: 0});
+        world.insert_resource(Rc::new(Cell::new(0)));
+        world.events_mut().send(Rc::new(1));
+        world.add_parallel_processor(Movement, 1);
+        world.add_processor(Logging, 1);
+
+        world.tick();
+        world.tick();
+        assert_eq!(*log.borrow(), vec![0, 0]);
+        assert_eq!(world.resource::<Rc<Cell<i32>>>().get(), 2);
+    }
+
+    #[test]
+    fn conflicting_processors_keep_their_order() {
+        let mut world = World::new();
+        let player = world.entities_mut().create_named("player");
+        world.components_mut().insert(player, Position{x: 0});
+        world.components_mut().insert(player, Velocity{x: 2});
+        world.components_mut().insert(player, Health{hp: 0});
+        world.components_mut().insert(player, Trail{xs: Vec::new()});
+
+        world.add_parallel_processor(Movement, 1);
+        world.add_parallel_processor(Regeneration, 1);
+        world.add_parallel_processor(Tracking, 1);
+        world.add_processor(Knockback, 2);
+
+        for _ in 0..4 {
+            world.tick();
+        }
+        let cm = world.components();
+        assert_eq!(cm.get::<Trail>(player).xs, vec![2, 0, -2, 0]);
+        assert_eq!(cm.get::<Position>(player).x, 0);
+        assert_eq!(cm.get::<Health>(player).hp, 4);
+        assert_eq!(cm.changed_since::<Position>(0), vec![player]);
+    }
+
+    #[test]
+    fn columns_are_restored_when_a_processor_panics() {
+        let mut world = World::new();
+        let player = world.entities_mut().create_named("player");
+        world.components_mut().insert(player, Position{x: 1});
+        world.components_mut().insert(player, Health{hp: 0});
+        world.add_parallel_processor(Faulty, 1);
+        world.add_parallel_processor(Regeneration, 1);
+
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| world.tick()));
+        assert!(result.is_err());
+        assert_eq!(world.components().get::<Position>(player).x, 1);
+        assert!(world.components().try_get::<Health>(player).is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "declared as written")]
+    fn access_is_declared_once() {
+        Access::new().write::<Position>().read::<Position>();
+    }
+
+    #[test]
+    #[should_panic(expected = "already declared as written")]
+    fn written_types_are_declared_once() {
+        Access::new().write::<Position>().write::<Position>();
+    }
+
+    #[test]
+    #[should_panic(expected = "already declared as read")]
+    fn read_types_are_declared_once() {
+        Access::new().read::<Velocity>().read::<Velocity>();
+    }
+
+    #[test]
+    fn accesses_conflict_on_writes() {
+        let movement = Access::new().read::<Velocity>().write::<Position>();
+        assert!(movement.conflicts(&Access::new().read::<Position>()));
+        assert!(Access::new().write::<Velocity>().conflicts(&movement));
+        assert!(!movement.conflicts(&Access::new().read::<Velocity>().write::<Health>()));
+    }
+}
+mod test_shared {
+    extern crate entity_system;
+    use std::sync::Arc;
+    use std::thread;
+    use entity_system::{EntityManager, EsError, SharedComponentManager, StorageKind};
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Mesh {
+        pub vertices: usize,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Position {
+        pub x: i32,
+    }
+
+    #[test]
+    fn types_are_locked_independently() {
+        let mut em = EntityManager::new();
+        let entities: Vec<_> = (0..100).map(|_| em.create()).collect();
+        let mut cm = SharedComponentManager::new();
+        cm.register::<Mesh>(StorageKind::Dense);
+        cm.register::<Position>(StorageKind::SparseSet);
+        let cm = Arc::new(cm);
+        for (i, &entity) in entities.iter().enumerate() {
+            cm.write::<Position>().insert(entity, Position{x: i as i32});
+        }
+
+        let loader = {
+            let (cm, entities) = (cm.clone(), entities.clone());
+            thread::spawn(move || {
+                for (i, &entity) in entities.iter().enumerate() {
+                    cm.write::<Mesh>().insert(entity, Mesh{vertices: i});
+                }
+            })
+        };
+        let positions = cm.read::<Position>();
+        let total: i32 = positions.iter().map(|meta| meta.component.x).sum();
+        loader.join().unwrap();
+        assert_eq!(total, 4950);
+        drop(positions);
+
+        assert_eq!(cm.read::<Mesh>().len(), 100);
+        assert_eq!(cm.read::<Mesh>().get(entities[7]), Some(&Mesh{vertices: 7}));
+        cm.write::<Position>().get_mut(entities[7]).unwrap().x = -1;
+        cm.write::<Position>().insert(entities[7], Position{x: 1});
+        assert_eq!(cm.read::<Position>().iter_for(entities[7]).count(), 2);
+        assert!(cm.purge(entities[7]));
+        assert!(!cm.read::<Mesh>().contains(entities[7]));
+        assert_eq!(cm.write::<Position>().remove_for(entities[8]), vec![Position{x: 8}]);
+        assert!(!cm.read::<Position>().contains(entities[8]));
+    }
+
+    #[test]
+    fn unregistered_types_are_an_error() {
+        let cm = SharedComponentManager::new();
+        assert!(matches!(cm.try_read::<Mesh>(), Err(EsError::NoSuchComponentType(_))));
+    }
+}
+mod test_commands {
+    extern crate entity_system;
+    use entity_system::{Access, Commands, EntityManager, ComponentManager, ParallelProcessor, View, World};
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Health {
+        pub hp: i32,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Corpse {
+        pub of: entity_system::EntityId,
+    }
+
+    #[test]
+    fn applies_changes_in_order() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        let alive = em.create();
+        let dead = em.create();
+        cm.insert(alive, Health{hp: 3});
+        cm.insert(dead, Health{hp: 0});
+
+        let mut commands = Commands::new();
+        for mut meta in cm.find_mut::<Health>() {
+            meta.component.hp -= 1;
+            if meta.component.hp < 0 {
+                let corpse = commands.create();
+                commands.insert(corpse, Corpse{of: meta.entity});
+                commands.insert(corpse, Health{hp: 9});
+                commands.remove::<Health, _>(corpse);
+                commands.destroy(meta.entity);
+            }
+        }
+        assert_eq!(commands.len(), 5);
+
+        let created = commands.apply(&mut em, &mut cm);
+        assert!(commands.is_empty());
+        assert_eq!(created.len(), 1);
+        assert!(!em.is_alive(dead));
+        assert_eq!(cm.find_entities_for_type::<Health>(), vec![alive]);
+        assert_eq!(cm.get::<Corpse>(created[0]), Corpse{of: dead});
+    }
+
+    #[test]
+    fn skips_changes_to_destroyed_entities() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        let gone = em.create();
+        cm.insert(gone, Health{hp: 1});
+
+        let mut commands = Commands::new();
+        let created = commands.create();
+        commands.destroy(created);
+        commands.insert(created, Health{hp: 2});
+        commands.destroy(gone);
+        commands.insert(gone, Health{hp: 3});
+
+        let created = commands.apply(&mut em, &mut cm);
+        assert!(!em.is_alive(created[0]));
+        assert!(!em.is_alive(gone));
+        assert!(!cm.has::<Health>(created[0]));
+        assert!(!cm.has::<Health>(gone));
+        assert!(!cm.has_components(gone));
+    }
+
+    struct Reaper {
+        commands: Commands,
+    }
+
+    impl ParallelProcessor for Reaper {
+        fn access(&self) -> Access {
+            Access::new().read::<Health>()
+        }
+
+        fn process(&mut self, view: &mut View) {
+            for meta in view.reads.iter::<Health>() {
+                if meta.component.hp <= 0 {
+                    let corpse = self.commands.create();
+                    self.commands.insert(corpse, Corpse{of: meta.entity});
+                    self.commands.destroy(meta.entity);
+                }
+            }
+        }
+
+        fn commands(&mut self) -> Option<&mut Commands> {
+            Some(&mut self.commands)
+        }
+    }
+
+    #[test]
+    fn worlds_apply_processor_commands() {
+        let mut world = World::new();
+        let dead = world.entities_mut().create();
+        world.components_mut().insert(dead, Health{hp: 0});
+        world.add_parallel_processor(Reaper{commands: Commands::new()}, 1);
+
+        world.tick();
+        assert!(!world.entities().is_alive(dead));
+        assert_eq!(world.components().find::<Corpse>().len(), 1);
+        world.tick();
+        assert_eq!(world.components().find::<Corpse>().len(), 1);
+    }
+}
+mod test_builder {
+    extern crate entity_system;
+    use entity_system::{ComponentManager, EntityManager, EsError, World};
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Position {
+        pub x: i32,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Velocity {
+        pub x: i32,
+    }
+
+    #[test]
+    fn builds_entities() {
+        let mut world = World::new();
+        let player = world.build_entity()
+            .with(Position{x: 1})
+            .with(Velocity{x: 2})
+            .named("player")
+            .build();
+        assert_eq!(world.entities().get_named("player"), Ok(player));
+        assert_eq!(world.components().get::<Velocity>(player), Velocity{x: 2});
+
+        let result = world.build_entity().with(Position{x: 3}).named("player").try_build();
+        assert_eq!(result, Err(EsError::DuplicateName("player".to_string())));
+        assert_eq!(world.components().find::<Position>().len(), 1);
+    }
+
+    fn check_batches(mut cm: ComponentManager) {
+        let mut em = EntityManager::new();
+        let entities: Vec<_> = (0..10).map(|_| em.create()).collect();
+        for (i, &entity) in entities.iter().enumerate() {
+            cm.insert_bundle(entity, (Position{x: i as i32}, Velocity{x: 1}));
+        }
+        cm.insert_bundle(entities[0], (Position{x: 100},));
+
+        let mut total = 0;
+        for (_, mut position, velocity) in cm.query::<(&mut Position, &Velocity)>() {
+            position.x += velocity.x;
+            total += position.x;
+        }
+        assert_eq!(total, 55);
+        assert_eq!(cm.find_for::<Position>(entities[0]), vec![Position{x: 1}, Position{x: 100}]);
+    }
+
+    #[test]
+    fn inserts_bundles_in_per_type_tables() {
+        check_batches(ComponentManager::new());
+    }
+
+    #[test]
+    fn inserts_bundles_with_archetypes() {
+        check_batches(ComponentManager::with_archetypes());
+    }
+
+    #[test]
+    fn spawns_batches() {
+        let mut world = World::new();
+        let spawned = world.spawn_batch((0..5).map(|i| (Position{x: i}, Velocity{x: -i})));
+        assert_eq!(spawned.len(), 5);
+        assert_eq!(world.components().get::<Velocity>(spawned[3]), Velocity{x: -3});
+    }
+}
+mod test_prefabs {
+    extern crate entity_system;
+    extern crate serde;
+    use entity_system::{EntityManager, ComponentManager, EsError, Format, Prefab, Registry};
+    use self::serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Health {
+        pub hp: u32,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Sprite {
+        pub image: String,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Loot {
+        pub gold: u32,
+    }
+
+    #[test]
+    fn spawns_with_overrides() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        let goblin = Prefab::new()
+            .with(Health{hp: 5})
+            .with(Sprite{image: "goblin.png".to_string()});
+
+        let grunt = goblin.spawn(&mut em, &mut cm);
+        let boss = goblin.clone()
+            .with(Health{hp: 50})
+            .spawn_named(&mut em, &mut cm, "boss")
+            .unwrap();
+
+        assert_eq!(goblin.get::<Health>(), Some(&Health{hp: 5}));
+        assert_eq!(cm.find_for::<Health>(grunt), vec![Health{hp: 5}]);
+        assert_eq!(cm.find_for::<Health>(boss), vec![Health{hp: 50}]);
+        assert_eq!(cm.get::<Sprite>(boss).image, "goblin.png");
+        assert_eq!(em.get_named("boss"), Ok(boss));
+    }
+
+    #[test]
+    fn loads_prefabs_from_data() {
+        let mut registry = Registry::new();
+        registry.register_cloneable::<Health>("Health");
+        registry.register_cloneable::<Sprite>("Sprite");
+        registry.register::<Loot>("Loot");
+
+        let data = r#"{
+            "goblin": {"Health": {"hp": 5}, "Sprite": {"image": "goblin.png"}},
+            "torch": {"Sprite": {"image": "torch.png"}}
+        }"#;
+        let prefabs = registry.load_prefabs(Format::Json, data.as_bytes()).unwrap();
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        let goblin = prefabs.spawn("goblin", &mut em, &mut cm).unwrap();
+        assert_eq!(cm.get::<Health>(goblin), Health{hp: 5});
+        assert_eq!(prefabs.get("torch").unwrap().len(), 1);
+        assert_eq!(prefabs.get("chest").err(), Some(EsError::NoSuchPrefab("chest".to_string())));
+
+        let chest = r#"{"chest": {"Loot": {"gold": 10}}}"#;
+        assert!(matches!(registry.load_prefabs(Format::Json, chest.as_bytes()), Err(EsError::Serialization(_))));
+    }
+}
+mod test_snapshot {
+    extern crate entity_system;
+    use entity_system::{ComponentManager, EsError, Snapshots, World};
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Position {
+        pub x: i32,
+        pub y: i32,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Health(u32);
+
+    pub struct Socket;
+
+    fn world() -> World
+    {
+        let mut world = World::new();
+        world.components_mut().register_cloneable::<Position>();
+        world.components_mut().register_cloneable::<Health>();
+        world
+    }
+
+    #[test]
+    fn restores_components_and_entities() {
+        let mut world = world();
+        let a = world.build_entity().with(Position{x: 1, y: 2}).with(Health(10)).named("a").build();
+        let b = world.build_entity().with(Position{x: 3, y: 4}).build();
+        let snapshot = world.snapshot();
+
+        world.components_mut().get_mut::<Position>(a).x = 100;
+        world.components_mut().get_mut::<Health>(a).0 = 0;
+        world.components_mut().remove_for::<Position>(b);
+        let c = world.build_entity().with(Health(5)).build();
+        world.entities_mut().destroy(b);
+        world.components_mut().purge(b);
+        world.tick();
+
+        world.restore(&snapshot);
+        assert_eq!(world.current_tick(), 0);
+        assert_eq!(world.components().get::<Position>(a), Position{x: 1, y: 2});
+        assert_eq!(world.components().get::<Health>(a), Health(10));
+        assert_eq!(world.components().get::<Position>(b), Position{x: 3, y: 4});
+        assert!(world.entities().is_alive(b));
+        assert!(!world.entities().is_alive(c));
+        assert!(world.components().try_get::<Health>(c).is_err());
+        assert_eq!(world.entities().get_named("a"), Ok(a));
+
+        // The snapshot can be restored again
+        world.components_mut().get_mut::<Position>(a).y = -1;
+        world.restore(&snapshot);
+        assert_eq!(world.components().get::<Position>(a), Position{x: 1, y: 2});
+    }
+
+    #[test]
+    fn restores_archetype_tables() {
+        let mut world = World::new();
+        *world.components_mut() = ComponentManager::with_archetypes();
+        world.components_mut().register_cloneable::<Position>();
+        world.components_mut().register_cloneable::<Health>();
+        let a = world.build_entity().with(Position{x: 1, y: 1}).build();
+        let snapshot = world.snapshot();
+
+        world.components_mut().insert(a, Health(3));
+        world.components_mut().get_mut::<Position>(a).x = 7;
+        world.restore(&snapshot);
+        assert_eq!(world.components().get::<Position>(a), Position{x: 1, y: 1});
+        assert!(world.components().try_get::<Health>(a).is_err());
+
+        world.components_mut().insert(a, Health(4));
+        assert_eq!(world.components().get::<Health>(a), Health(4));
+    }
+
+    #[test]
+    fn requires_cloneable_types() {
+        let mut world = world();
+        let a = world.build_entity().with(Socket).build();
+        assert!(matches!(world.try_snapshot(), Err(EsError::NotCloneable(_))));
+
+        world.components_mut().remove_for::<Socket>(a);
+        assert!(world.try_snapshot().is_ok());
+    }
+
+    #[test]
+    fn keeps_the_last_snapshots() {
+        let mut world = world();
+        let a = world.build_entity().with(Position{x: 0, y: 0}).build();
+        let mut snapshots = Snapshots::new(3);
+        for _ in 0..5 {
+            snapshots.push(world.snapshot());
+            world.components_mut().get_mut::<Position>(a).x += 1;
+            world.tick();
+        }
+        assert_eq!(snapshots.len(), 3);
+        assert!(snapshots.get(1).is_none());
+        assert_eq!(snapshots.latest().map(|snapshot| snapshot.tick()), Some(4));
+
+        world.restore(snapshots.get(2).unwrap());
+        snapshots.discard_after(2);
+        assert_eq!(world.current_tick(), 2);
+        assert_eq!(world.components().get::<Position>(a).x, 2);
+        assert_eq!(snapshots.len(), 1);
+    }
+}
+mod test_replication {
+    extern crate entity_system;
+    extern crate serde;
+    use std::sync::mpsc;
+    use entity_system::{ComponentManager, EntityManager, ReplicatedTypes, ReplicationClient, ReplicationServer};
+    use self::serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Position {
+        pub x: i32,
+        pub y: i32,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Health(u32);
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct ServerOnly;
+
+    struct Client {
+        em: EntityManager,
+        cm: ComponentManager,
+        replication: ReplicationClient,
+    }
+
+    fn types() -> ReplicatedTypes
+    {
+        let mut types = ReplicatedTypes::new();
+        types.register::<Position>();
+        types.register::<Health>();
+        types
+    }
+
+    fn client() -> Client
+    {
+        let mut em = EntityManager::new();
+        // Offset the client's ids from the server's
+        em.create();
+        em.create();
+        Client{em, cm: ComponentManager::new(), replication: ReplicationClient::new(types())}
+    }
+
+    fn sync(server: &mut ReplicationServer, cm: &mut ComponentManager, client: &mut Client)
+    {
+        let (sender, receiver) = mpsc::channel();
+        let mut diff = Vec::new();
+        server.write_diff(cm, &mut diff).unwrap();
+        sender.send(diff).unwrap();
+        let diff = receiver.recv().unwrap();
+        client.replication.apply(&diff[..], &mut client.em, &mut client.cm).unwrap();
+    }
+
+    #[test]
+    fn mirrors_server_changes() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        let mut server = ReplicationServer::new(types());
+        let mut client = client();
+
+        let a = em.create();
+        let b = em.create();
+        let hidden = em.create();
+        cm.insert(a, Position{x: 1, y: 2});
+        cm.insert(a, Health(10));
+        cm.insert(b, Position{x: 5, y: 5});
+        cm.insert(hidden, ServerOnly);
+        sync(&mut server, &mut cm, &mut client);
+
+        let local_a = client.replication.client_id(a).unwrap();
+        let local_b = client.replication.client_id(b).unwrap();
+        assert_ne!(local_a, a);
+        assert_eq!(client.replication.server_id(local_a), Some(a));
+        assert_eq!(client.replication.client_id(hidden), None);
+        assert_eq!(client.cm.get::<Position>(local_a), Position{x: 1, y: 2});
+        assert_eq!(client.cm.get::<Health>(local_a), Health(10));
+        assert!(!client.cm.contains::<ServerOnly>());
+
+        cm.get_mut::<Position>(a).x = 3;
+        cm.remove_for::<Health>(a);
+        cm.insert(b, Health(7));
+        cm.insert(b, Health(8));
+        sync(&mut server, &mut cm, &mut client);
+        assert_eq!(client.cm.get::<Position>(local_a), Position{x: 3, y: 2});
+        assert!(client.cm.try_get::<Health>(local_a).is_err());
+        assert_eq!(client.cm.find_for::<Health>(local_b), vec![Health(7), Health(8)]);
+
+        em.destroy(b);
+        cm.purge(b);
+        cm.remove_for::<Position>(a);
+        cm.insert(a, Health(1));
+        sync(&mut server, &mut cm, &mut client);
+        assert!(!client.em.is_alive(local_b));
+        assert_eq!(client.replication.client_id(b), None);
+        assert!(client.em.is_alive(local_a));
+        assert!(client.cm.try_get::<Position>(local_a).is_err());
+        assert_eq!(client.cm.get::<Health>(local_a), Health(1));
+
+        cm.remove_for::<Health>(a);
+        sync(&mut server, &mut cm, &mut client);
+        assert!(!client.em.is_alive(local_a));
+    }
+
+    #[test]
+    fn sends_only_changes() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        let mut server = ReplicationServer::new(types());
+        let a = em.create();
+        cm.insert(a, Position{x: 0, y: 0});
+
+        let mut first = Vec::new();
+        server.write_diff(&mut cm, &mut first).unwrap();
+        let mut idle = Vec::new();
+        server.write_diff(&mut cm, &mut idle).unwrap();
+        assert!(idle.len() < first.len());
+
+        // Entities that come and go between diffs are never sent
+        let brief = em.create();
+        cm.insert(brief, Health(3));
+        cm.purge(brief);
+        let mut diff = Vec::new();
+        server.write_diff(&mut cm, &mut diff).unwrap();
+        assert_eq!(diff, idle);
+    }
+
+    #[test]
+    fn catches_up_late_clients() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        let mut server = ReplicationServer::new(types());
+        let a = em.create();
+        cm.insert(a, Position{x: 4, y: 4});
+        server.write_diff(&mut cm, &mut Vec::new()).unwrap();
+
+        let mut late = client();
+        let mut full = Vec::new();
+        server.write_full(&cm, &mut full).unwrap();
+        late.replication.apply(&full[..], &mut late.em, &mut late.cm).unwrap();
+        let local_a = late.replication.client_id(a).unwrap();
+        assert_eq!(late.cm.get::<Position>(local_a), Position{x: 4, y: 4});
+
+        cm.get_mut::<Position>(a).y = 9;
+        sync(&mut server, &mut cm, &mut late);
+        assert_eq!(late.cm.get::<Position>(local_a), Position{x: 4, y: 9});
+    }
+
+    #[test]
+    fn resync_reuses_mirrored_entities() {
+        let mut em = EntityManager::new();
+        let mut cm = ComponentManager::new();
+        let mut server = ReplicationServer::new(types());
+        let mut client = client();
+        let a = em.create();
+        cm.insert(a, Position{x: 1, y: 1});
+        cm.insert(a, Health(5));
+        sync(&mut server, &mut cm, &mut client);
+        let local_a = client.replication.client_id(a).unwrap();
+        client.cm.insert(local_a, Health(6));
+
+        cm.remove_for::<Health>(a);
+        server.write_diff(&mut cm, &mut Vec::new()).unwrap();
+        let mut full = Vec::new();
+        server.write_full(&cm, &mut full).unwrap();
+        client.replication.apply(&full[..], &mut client.em, &mut client.cm).unwrap();
+
+        assert_eq!(client.replication.client_id(a), Some(local_a));
+        assert_eq!(client.cm.find_for::<Position>(local_a), vec![Position{x: 1, y: 1}]);
+        assert!(client.cm.try_get::<Health>(local_a).is_err());
+        assert_eq!(client.cm.iter::<Position>().count(), 1);
+    }
+}
+mod test_introspection {
+    extern crate entity_system;
+    extern crate serde;
+    use std::any::TypeId;
+    use entity_system::{ComponentManager, EntityManager, Registry, StorageKind};
+    use self::serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Position {
+        pub x: i32,
+        pub y: i32,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Buff(u32);
+
+    pub struct Selected;
+
+    fn check(mut cm: ComponentManager) {
+        let mut registry = Registry::new();
+        registry.register::<Position>("Position");
+        let mut em = EntityManager::new();
+        let a = em.create();
+        let b = em.create();
+        cm.insert(a, Position{x: 1, y: 1});
+        cm.insert(a, Buff(1));
+        cm.insert(a, Buff(2));
+        cm.insert(a, Selected);
+        cm.insert(b, Buff(3));
+
+        assert!(cm.has::<Position>(a));
+        assert!(!cm.has::<Position>(b));
+        assert_eq!(cm.count::<Buff>(a), 2);
+        assert_eq!(cm.count::<Buff>(b), 1);
+        assert_eq!(cm.count::<Selected>(b), 0);
+
+        let types: Vec<TypeId> = cm.component_types_of(a, &registry).iter().map(|t| t.type_id).collect();
+        assert_eq!(types.len(), 3);
+        assert!(types.contains(&TypeId::of::<Position>()));
+        assert!(types.contains(&TypeId::of::<Buff>()));
+        assert!(types.contains(&TypeId::of::<Selected>()));
+        let position = cm.component_types_of(a, &registry).into_iter()
+            .find(|t| t.type_id == TypeId::of::<Position>())
+            .unwrap();
+        assert_eq!(position.name, Some("Position"));
+        assert_eq!(position.type_name, std::any::type_name::<Position>());
+        let names: Vec<(&str, Option<&str>)> = cm.component_types_of(b, &registry).iter().map(|t| (t.type_name, t.name)).collect();
+        assert_eq!(names, vec![(std::any::type_name::<Buff>(), None)]);
+
+        assert!(cm.entity_exists(&em, a));
+        assert!(cm.has_components(a));
+        cm.purge(a);
+        assert!(cm.entity_exists(&em, a));
+        assert!(!cm.has_components(a));
+        em.destroy(a);
+        assert!(!cm.entity_exists(&em, a));
+        assert!(cm.component_types_of(a, &registry).is_empty());
+        assert!(!cm.has::<Position>(a));
+    }
+
+    #[test]
+    fn lists_components_of_an_entity() {
+        check(ComponentManager::new());
+    }
+
+    #[test]
+    fn lists_components_of_an_entity_in_archetypes() {
+        let mut cm = ComponentManager::with_archetypes();
+        cm.register::<Selected>(StorageKind::SparseSet);
+        check(cm);
+    }
+
+    #[test]
+    fn names_registered_types() {
+        let mut registry = Registry::new();
+        registry.register::<Position>("Position");
+        assert_eq!(registry.name_of_type(TypeId::of::<Position>()), Some("Position"));
+        assert_eq!(registry.name_of_type(TypeId::of::<Buff>()), None);
+    }
+}
+mod test_reflection {
+    extern crate entity_system;
+    extern crate serde;
+    extern crate serde_json;
+    use std::any::TypeId;
+    use entity_system::{ComponentManager, EntityManager, EsError, Registry};
+    use self::serde::{Deserialize, Serialize};
+    use self::serde_json::Value;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Position {
+        pub x: i32,
+        pub y: i32,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Color(u8, u8, u8);
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Tag(String);
+
+    pub struct Hidden;
+
+    fn setup() -> (Registry, ComponentManager, EntityManager)
+    {
+        let mut registry = Registry::new();
+        registry.register::<Position>("Position");
+        registry.register::<Color>("Color");
+        registry.register::<Tag>("Tag");
+        registry.register_debug::<Position>();
+        registry.register_debug::<Tag>();
+        (registry, ComponentManager::new(), EntityManager::new())
+    }
+
+    #[test]
+    fn dumps_components_as_text() {
+        let (registry, mut cm, mut em) = setup();
+        let a = em.create();
+        cm.insert(a, Tag("b".to_string()));
+        cm.insert(a, Position{x: 1, y: 2});
+        cm.insert(a, Tag("a".to_string()));
+        cm.insert(a, Color(255, 0, 0));
+        cm.insert(a, Hidden);
+
+        assert_eq!(registry.name_of_type(TypeId::of::<Position>()), Some("Position"));
+        assert_eq!(registry.type_named("Tag"), Some(TypeId::of::<Tag>()));
+        assert_eq!(registry.dump(&cm, a), format!(
+            "Color: [255,0,0]\nPosition: Position {{ x: 1, y: 2 }}\nTag: Tag(\"b\")\nTag: Tag(\"a\")\n{}",
+            std::any::type_name::<Hidden>()));
+    }
+
+    #[test]
+    fn reads_and_writes_fields_by_name() {
+        let (registry, mut cm, mut em) = setup();
+        let a = em.create();
+        cm.insert(a, Position{x: 1, y: 2});
+        cm.insert(a, Color(255, 0, 0));
+
+        assert_eq!(registry.field_names(&cm, a, "Position"), Ok(vec!["x".to_string(), "y".to_string()]));
+        assert_eq!(registry.field_names(&cm, a, "Color"), Ok(vec!["0".to_string(), "1".to_string(), "2".to_string()]));
+        assert_eq!(registry.field(&cm, a, "Position", "y"), Ok(Value::from(2)));
+
+        let since = cm.increment_change_tick();
+        registry.set_field(&mut cm, a, "Position", "x", Value::from(10)).unwrap();
+        registry.set_field(&mut cm, a, "Color", "2", Value::from(128)).unwrap();
+        assert_eq!(cm.get::<Position>(a), Position{x: 10, y: 2});
+        assert_eq!(cm.get::<Color>(a), Color(255, 0, 128));
+        assert_eq!(cm.changed_since::<Position>(since), vec![a]);
+    }
+
+    #[test]
+    fn reports_bad_field_access() {
+        let (registry, mut cm, mut em) = setup();
+        let a = em.create();
+        let b = em.create();
+        cm.insert(a, Position{x: 1, y: 2});
+        cm.insert(b, Color(0, 0, 0));
+
+        assert_eq!(registry.field(&cm, a, "Velocity", "x"), Err(EsError::UnregisteredComponentType("Velocity".to_string())));
+        assert_eq!(registry.field(&cm, a, "Position", "z"), Err(EsError::NoSuchField("Position".to_string(), "z".to_string())));
+        assert!(matches!(registry.field(&cm, b, "Position", "x"), Err(EsError::EntityLacksComponent(..))));
+
+        let since = cm.increment_change_tick();
+        assert!(matches!(registry.set_field(&mut cm, a, "Position", "x", Value::from("left")), Err(EsError::InvalidFieldValue(..))));
+        assert_eq!(cm.get::<Position>(a), Position{x: 1, y: 2});
+        assert!(cm.changed_since::<Position>(since).is_empty());
+    }
 }